@@ -5,26 +5,59 @@ use std::{
 		consts::{ARCH, OS},
 		current_exe
 	},
+	fmt::{Display, Formatter, Result as _Result},
 	fs::metadata,
 	iter::Skip,
-	net::Ipv4Addr,
+	net::{Ipv4Addr, SocketAddr},
 	process::exit,
 	thread::available_parallelism
 };
 use crate::{
 	common::Result,
 	model::Model,
-	protocol::Version
+	protocol::Version,
+	storage::FsyncPolicy
 };
 
+pub enum Bind {
+	Tcp(SocketAddr),
+	#[cfg(unix)]
+	Unix(String)
+}
+
+impl Display for Bind {
+	fn fmt(self: &Self, formatter: &mut Formatter<'_>) -> _Result {
+		match self {
+			Bind::Tcp(address) => write!(formatter, "{}", address),
+			#[cfg(unix)]
+			Bind::Unix(path) => write!(formatter, "{}", path)
+		}
+	}
+}
+
 pub struct Argument {
 	pub model: Model,
 	pub capacity: usize,
 	pub directory: String,
+	pub storage_mirrors: Vec<String>,
 	pub host: Ipv4Addr,
 	pub port: u16,
+	pub binds: Vec<Bind>,
 	pub thread_count: usize,
+	pub handshake_timeout: u64,
+	pub read_timeout: u64,
 	pub is_verbose: bool,
+	pub is_flush_allowed: bool,
+	pub slowlog_slower_than: u64,
+	pub slowlog_max_len: u32,
+	pub list_max_listpack_entries: usize,
+	pub hash_max_listpack_entries: usize,
+	pub hash_max_listpack_value: usize,
+	pub fsync_policy: FsyncPolicy,
+	pub expire_interval: u64,
+	pub retrain_interval: u64,
+	pub retrain_lookback: u64,
+	pub retrain_script: Option<String>,
 	pub version: Version,
 	pub platform: String
 }
@@ -36,15 +69,30 @@ const DEFAULT_DIRECTORY: &str = if cfg!(target_os = "windows") {
 };
 
 impl Argument {
-	pub fn new() -> Result<Self> {
-		let mut argument: Argument = Argument {
+	pub fn defaults() -> Result<Self> {
+		Ok(Argument {
 			model: Model::DeepQNetwork,
 			capacity: 128,
 			directory: DEFAULT_DIRECTORY.to_string(),
+			storage_mirrors: Vec::new(),
 			host: Ipv4Addr::new(127, 0, 0, 1),
 			port: 5190,
+			binds: Vec::new(),
 			thread_count: available_parallelism()?.get() * 2,
+			handshake_timeout: 5,
+			read_timeout: 60,
 			is_verbose: false,
+			is_flush_allowed: false,
+			slowlog_slower_than: 10000,
+			slowlog_max_len: 128,
+			list_max_listpack_entries: 128,
+			hash_max_listpack_entries: 128,
+			hash_max_listpack_value: 64,
+			fsync_policy: FsyncPolicy::No,
+			expire_interval: 1000,
+			retrain_interval: 0,
+			retrain_lookback: 300,
+			retrain_script: None,
 			version: Version::try_from(env!("CARGO_PKG_VERSION"))?,
 			platform: format!("{}-{}-{}{}", ARCH, OS, if cfg!(target_vendor = "apple") {
 				"apple"
@@ -65,7 +113,121 @@ impl Argument {
 			} else {
 				""
 			})
-		};
+		})
+	}
+
+	// parameter names are the long option names without leading dashes
+	pub fn apply(self: &mut Self, name: &str, value: String) -> Result<()> {
+		match name {
+			"model" => match value.to_ascii_lowercase()
+				.as_str() {
+				"dqn" | "deepqnetwork" => self.model = Model::DeepQNetwork,
+				"lru" | "leastrecentlyused" => self.model = Model::LeastRecentlyUsed,
+				"lfu" | "leastfrequentlyused" => self.model = Model::LeastFrequentlyUsed,
+				_ => return Err(Box::from("model must be one of dqn, lru, lfu"))
+			},
+			"capacity" => {
+				self.capacity = value.parse::<usize>()?;
+
+				if self.capacity == 0 {
+					return Err(Box::from("capacity must be greater than 0"));
+				}
+			},
+			"directory" => {
+				if !metadata(&value)?.is_dir() {
+					return Err(Box::from("directory must be folder"));
+				}
+
+				self.directory = value;
+			},
+			"storage-mirror" => self.storage_mirrors.push(value),
+			"host" => self.host = value.parse::<Ipv4Addr>()?,
+			"port" => {
+				self.port = value.parse::<u16>()?;
+
+				if self.port == 0 {
+					return Err(Box::from("port must be greater than 0"));
+				}
+			},
+			"bind" => self.binds.push(if let Ok(address) = value.parse::<SocketAddr>() {
+				Bind::Tcp(address)
+			} else {
+				#[cfg(unix)] {
+					Bind::Unix(value)
+				}
+				#[cfg(not(unix))] {
+					return Err(Box::from("bind must be socket address"));
+				}
+			}),
+			"threadcount" => {
+				self.thread_count = value.parse::<usize>()?;
+
+				if self.thread_count == 0 {
+					return Err(Box::from("thread count must be greater than 0"));
+				}
+			},
+			"handshake-timeout" => {
+				self.handshake_timeout = value.parse::<u64>()?;
+
+				if self.handshake_timeout == 0 {
+					return Err(Box::from("handshake timeout must be greater than 0"));
+				}
+			},
+			"read-timeout" => {
+				self.read_timeout = value.parse::<u64>()?;
+
+				if self.read_timeout == 0 {
+					return Err(Box::from("read timeout must be greater than 0"));
+				}
+			},
+			"verbose" => self.is_verbose = value.parse::<bool>()?,
+			"allow-flush" => self.is_flush_allowed = value.parse::<bool>()?,
+			"slowlog-slower-than" => self.slowlog_slower_than = value.parse::<u64>()?,
+			"slowlog-max-len" => self.slowlog_max_len = value.parse::<u32>()?,
+			"list-max-listpack-entries" => self.list_max_listpack_entries = value.parse::<usize>()?,
+			"hash-max-listpack-entries" => self.hash_max_listpack_entries = value.parse::<usize>()?,
+			"hash-max-listpack-value" => self.hash_max_listpack_value = value.parse::<usize>()?,
+			"fsync-policy" => match value.to_ascii_lowercase()
+				.as_str() {
+				"always" => self.fsync_policy = FsyncPolicy::Always,
+				"everysec" => self.fsync_policy = FsyncPolicy::EverySecond,
+				"no" => self.fsync_policy = FsyncPolicy::No,
+				_ => return Err(Box::from("fsync policy must be one of always, everysec, no"))
+			},
+			"expire-interval" => {
+				self.expire_interval = value.parse::<u64>()?;
+
+				if self.expire_interval == 0 {
+					return Err(Box::from("expire interval must be greater than 0"));
+				}
+			},
+			"retrain-interval" => self.retrain_interval = value.parse::<u64>()?,
+			"retrain-lookback-seconds" => {
+				self.retrain_lookback = value.parse::<u64>()?;
+
+				if self.retrain_lookback == 0 {
+					return Err(Box::from("retrain lookback must be greater than 0"));
+				}
+			},
+			"retrain-script" => {
+				if !metadata(&value)?.is_file() {
+					return Err(Box::from("retrain script must be file"));
+				}
+
+				self.retrain_script = Some(value);
+			},
+			_ => return Err(Box::from(format!("parameter {:?} must be known", name)))
+		}
+
+		Ok(())
+	}
+
+	pub fn validate(name: &str, value: String) -> Result<()> {
+		Argument::defaults()?.apply(name, value)
+	}
+
+	pub fn new() -> Result<Self> {
+		let mut argument: Argument = Argument::defaults()?;
 
 		let file_name: String = if let Some(raw_file_name) = current_exe()?.file_name() {
 			raw_file_name.display()
@@ -77,56 +239,37 @@ impl Argument {
 		let mut arguments: Skip<Args> = args().skip(1);
 
 		while let Some(value) = arguments.next() {
-			match value.as_str() {
-				"--model" | "-m" => if let Some(raw_model) = arguments.next() {
-					match raw_model.to_ascii_lowercase()
-						.as_str() {
-						"dqn" | "deepqnetwork" => argument.model = Model::DeepQNetwork,
-						"lru" | "leastrecentlyused" => argument.model = Model::LeastRecentlyUsed,
-						"lfu" | "leastfrequentlyused" => argument.model = Model::LeastFrequentlyUsed,
-						_ => return Err(Box::from("model must be one of dqn, lru, lfu"))
-					}
-				} else {
-					return Err(Box::from("model must be provided"));
-				}
-				"--capacity" | "-c" => if let Some(raw_capacity) = arguments.next() {
-					argument.capacity = raw_capacity.parse::<usize>()?;
+			let name: &str = match value.as_str() {
+				"--model" | "-m" => "model",
+				"--capacity" | "-c" => "capacity",
+				"--directory" | "-d" => "directory",
+				"--storage-mirror" => "storage-mirror",
+				"--host" | "-H" => "host",
+				"--port" | "-p" => "port",
+				"--bind" | "-b" => "bind",
+				"--threadcount" | "-t" => "threadcount",
+				"--handshake-timeout" => "handshake-timeout",
+				"--read-timeout" => "read-timeout",
+				"--slowlog-slower-than" => "slowlog-slower-than",
+				"--slowlog-max-len" => "slowlog-max-len",
+				"--list-max-listpack-entries" => "list-max-listpack-entries",
+				"--hash-max-listpack-entries" => "hash-max-listpack-entries",
+				"--hash-max-listpack-value" => "hash-max-listpack-value",
+				"--fsync-policy" => "fsync-policy",
+				"--expire-interval" => "expire-interval",
+				"--retrain-interval" => "retrain-interval",
+				"--retrain-lookback-seconds" => "retrain-lookback-seconds",
+				"--retrain-script" => "retrain-script",
+				"--verbose" | "-v" => {
+					argument.is_verbose = true;
 
-					if argument.capacity == 0 {
-						return Err(Box::from("capacity must be greater than 0"))
-					}
-				} else {
-					return Err(Box::from("capacity must be provided"));
+					continue;
 				},
-				"--directory" | "-d" => if let Some(directory) = arguments.next() {
-					argument.directory = directory;
+				"--allow-flush" => {
+					argument.is_flush_allowed = true;
 
-					if !metadata(&argument.directory)?.is_dir() {
-						return Err(Box::from("directory must be folder"));
-					}
-				} else {
-					return Err(Box::from("directory must be provided"));
-				}
-				"--host" | "-H" => if let Some(raw_host) = arguments.next() {
-					argument.host = raw_host.parse::<Ipv4Addr>()?;
-				} else {
-					return Err(Box::from("host must be provided"));
+					continue;
 				},
-				"--port" | "-p" => if let Some(raw_port) = arguments.next() {
-					argument.port = raw_port.parse::<u16>()?;
-
-					if argument.port == 0 {
-						return Err(Box::from("port must be greater than 0"));
-					}
-				},
-				"--threadcount" | "-t" => if let Some(raw_thread_count) = arguments.next() {
-					argument.thread_count = raw_thread_count.parse::<usize>()?;
-
-					if argument.thread_count == 0 {
-						return Err(Box::from("thread count must be greater than 0"));
-					}
-				},
-				"--verbose" | "-v" => argument.is_verbose = true,
 				"--version" | "-V" => {
 					print!("{} {}\n", file_name, argument.version);
 
@@ -139,10 +282,35 @@ Options:
 	-m, --model <MODEL>          Set cache model [dqn, lru, lfu] (default: dqn)
 	-c, --capacity <CAPACITY>    Set cache capacity (default: 128)
 	-d, --directory <DIRECTORY>  Set data directory (default: {})
+	    --storage-mirror <DIRECTORY>
+	                             Mirror data to directory, repeatable
 	-H, --host <HOST>            Set server host (default: 127.0.0.1)
 	-p, --port <PORT>            Set server port (default: 5190)
+	-b, --bind <ADDRESS>         Bind address or unix socket path, repeatable (default: HOST:PORT)
 	-t, --threadcount <COUNT>    Set thread count (default: number of logical cores * 2)
+	    --handshake-timeout <SECONDS>
+	                             Set handshake timeout (default: 5)
+	    --read-timeout <SECONDS> Set read timeout after handshake (default: 60)
 	-v, --verbose                Enable verbose output
+	    --allow-flush            Allow clients to wipe every key with FLUSH
+	    --slowlog-slower-than <MICROSECONDS>
+	                             Log operations slower than this (default: 10000)
+	    --slowlog-max-len <LENGTH>
+	                             Set slow log length (default: 128)
+	    --list-max-listpack-entries <COUNT>
+	                             Convert larger lists to linked lists (default: 128)
+	    --hash-max-listpack-entries <COUNT>
+	                             Convert hashes with more fields to hash tables (default: 128)
+	    --hash-max-listpack-value <BYTES>
+	                             Convert hashes with a longer value to hash tables (default: 64)
+	    --fsync-policy <POLICY>  Set storage fsync policy [always, everysec, no] (default: no)
+	    --expire-interval <MILLISECONDS>
+	                             Remove expired keys this often (default: 1000)
+	    --retrain-interval <HOURS>
+	                             Retrain the dqn model this often, 0 to never (default: 0)
+	    --retrain-lookback-seconds <SECONDS>
+	                             Count an eviction as bad if the key returns this soon (default: 300)
+	    --retrain-script <PATH>  Python script to retrain with, given observations csv and model output paths
 	-V, --version                Print version information
 	-h, --help                   Print this help message
 ", file_name, DEFAULT_DIRECTORY);
@@ -151,11 +319,33 @@ Options:
 				},
 				"--" => if let Some(_) = arguments.next() {
 					return Err(Box::from("positional arguments must not be provided"));
+				} else {
+					continue;
 				},
-				_ => return Err(Box::from(format!("Usage: {} [-m <MODEL>] [-c <CAPACITY>] [-d <DIRECTORY>] [-H <HOST>] [-p <PORT>] [-t <COUNT>] [-v] [-V] [-h]", file_name)))
+				_ => return Err(Box::from(format!("Usage: {} [-m <MODEL>] [-c <CAPACITY>] [-d <DIRECTORY>] [--storage-mirror <DIRECTORY>]... [-H <HOST>] [-p <PORT>] [-b <ADDRESS>]... [-t <COUNT>] [--handshake-timeout <SECONDS>] [--read-timeout <SECONDS>] [-v] [--allow-flush] [--slowlog-slower-than <MICROSECONDS>] [--slowlog-max-len <LENGTH>] [--list-max-listpack-entries <COUNT>] [--hash-max-listpack-entries <COUNT>] [--hash-max-listpack-value <BYTES>] [--fsync-policy <POLICY>] [--expire-interval <MILLISECONDS>] [--retrain-interval <HOURS>] [--retrain-lookback-seconds <SECONDS>] [--retrain-script <PATH>] [-V] [-h]", file_name)))
+			};
+
+			if let Some(raw_value) = arguments.next() {
+				argument.apply(name, raw_value)?;
+			} else {
+				return Err(Box::from(format!("{} must be provided", name.replace('-', " "))));
 			}
 		}
 
+		if argument.retrain_interval != 0 {
+			if argument.retrain_script.is_none() {
+				return Err(Box::from("retrain script must be provided with retrain interval"));
+			}
+
+			if !matches!(argument.model, Model::DeepQNetwork) {
+				return Err(Box::from("model must be dqn to be retrained"));
+			}
+		}
+
+		if argument.binds.len() == 0 {
+			argument.binds.push(Bind::Tcp(SocketAddr::from((argument.host, argument.port))));
+		}
+
 		Ok(argument)
 	}
 }
\ No newline at end of file
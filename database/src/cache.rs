@@ -1,28 +1,140 @@
 use std::{
 	collections::HashMap,
-	fmt::{Debug, Formatter, Result as _Result}
+	fmt::{Debug, Formatter, Result as _Result},
+	num::ParseIntError,
+	sync::{Arc, Mutex, MutexGuard, PoisonError},
+	time::Instant
 };
 use crate::{
-	common::{ARGUMENT, Result, unix_epoch},
+	common::{ARGUMENT, Result, random_sample, unix_epoch, unix_epoch_millis},
 	debug,
+	hash::Hash,
 	info,
-	model::{DeepQNetwork, LeastFrequentlyUsed, LeastRecentlyUsed, Model}
+	keyspace::{Keyspace, KeyspaceEvent},
+	list::List,
+	model::{DeepQNetwork, LeastFrequentlyUsed, LeastRecentlyUsed, Model, features},
+	retrain::{Observation, Observations},
+	sorted_set::SortedSet,
+	stats::Stats
 };
 
+// storage keeps strings as they are and tags anything else with a byte no UTF-8 text can contain
+const TYPED_VALUE: u8 = 0xFF;
+const LIST_VALUE: u8 = 0x00;
+const HASH_VALUE: u8 = 0x01;
+const SORTED_SET_VALUE: u8 = 0x02;
+
+#[derive(Clone)]
+pub enum Value {
+	String(String),
+	List(List),
+	Hash(Hash),
+	SortedSet(SortedSet)
+}
+
+impl Value {
+	pub fn from_bytes(bytes: Vec<u8>) -> Result<Value> {
+		if bytes.first() != Some(&TYPED_VALUE) {
+			return Ok(Value::String(String::from_utf8(bytes)?));
+		}
+
+		match bytes.get(1) {
+			Some(&LIST_VALUE) => Ok(Value::List(List::from_bytes(bytes[2..].to_vec())?)),
+			Some(&HASH_VALUE) => Ok(Value::Hash(Hash::from_bytes(&bytes[2..])?)),
+			Some(&SORTED_SET_VALUE) => Ok(Value::SortedSet(SortedSet::from_bytes(&bytes[2..])?)),
+			_ => Err(Box::from("value type must be known"))
+		}
+	}
+
+	pub fn to_bytes(self: &Self) -> Vec<u8> {
+		match self {
+			Value::String(value) => value.as_bytes()
+				.to_vec(),
+			Value::List(list) => {
+				let mut bytes: Vec<u8> = vec![TYPED_VALUE, LIST_VALUE];
+
+				bytes.extend(list.to_bytes());
+
+				bytes
+			},
+			Value::Hash(hash) => {
+				let mut bytes: Vec<u8> = vec![TYPED_VALUE, HASH_VALUE];
+
+				bytes.extend(hash.to_bytes());
+
+				bytes
+			},
+			Value::SortedSet(sorted_set) => {
+				let mut bytes: Vec<u8> = vec![TYPED_VALUE, SORTED_SET_VALUE];
+
+				bytes.extend(sorted_set.to_bytes());
+
+				bytes
+			}
+		}
+	}
+
+	pub fn size(self: &Self) -> usize {
+		match self {
+			Value::String(value) => value.len(),
+			Value::List(list) => list.size(),
+			Value::Hash(hash) => hash.size(),
+			Value::SortedSet(sorted_set) => sorted_set.size()
+		}
+	}
+
+	// strings are never empty, collections are dropped once they become so
+	pub fn is_empty(self: &Self) -> bool {
+		match self {
+			Value::String(value) => value.len() == 0,
+			Value::List(list) => list.len() == 0,
+			Value::Hash(hash) => hash.len() == 0,
+			Value::SortedSet(sorted_set) => sorted_set.len() == 0
+		}
+	}
+
+	// shared by every counter operation, so they all reject the same inputs
+	pub fn as_integer(self: &Self) -> Result<i64> {
+		if let Value::String(value) = self {
+			Ok(value.parse::<i64>()
+				.map_err(|_: ParseIntError| "value must be an integer")?)
+		} else {
+			Err(Box::from("value must be a string"))
+		}
+	}
+
+	pub fn encoding(self: &Self) -> &'static str {
+		match self {
+			Value::String(_) => "raw",
+			Value::List(list) => list.encoding(),
+			Value::Hash(hash) => hash.encoding(),
+			Value::SortedSet(_) => "skiplist"
+		}
+	}
+}
+
 pub struct Entry {
-	pub value: String,
+	pub value: Value,
 	pub accessed_at: u64,
-	pub access_count: u64
+	pub access_count: u64,
+	// milliseconds since the epoch, unlike accessed_at which the evictor reads in seconds
+	pub expires_at: Option<u64>
 }
 
 impl Entry {
-	pub fn new(value: &str) -> Result<Entry> {
+	pub fn new(value: Value) -> Result<Entry> {
 		Ok(Entry {
-			value: value.to_owned(),
+			value: value,
 			accessed_at: unix_epoch()?,
-			access_count: 1
+			access_count: 1,
+			expires_at: None
 		})
 	}
+
+	pub fn is_expired(self: &Self, now: u64) -> bool {
+		self.expires_at
+			.is_some_and(|expires_at: u64| expires_at <= now)
+	}
 }
 
 pub trait Evictor {
@@ -32,21 +144,53 @@ pub trait Evictor {
 impl Debug for Entry {
 	fn fmt(self: &Self, formatter: &mut Formatter<'_>) -> _Result {
 		formatter.debug_struct("")
-			.field("size", &self.value.len())
+			.field("size", &self.value.size())
 			.field("accessed_at", &self.accessed_at)
 			.field("access_count", &self.access_count)
+			.field("expires_at", &self.expires_at)
 			.finish()
 	}
 }
 
+struct KeySize<'a>(&'a str, usize);
+
+impl<'a> Debug for KeySize<'a> {
+	fn fmt(self: &Self, formatter: &mut Formatter<'_>) -> _Result {
+		write!(formatter, "{:?}({}B)", self.0, self.1)
+	}
+}
+
 pub struct Cache {
 	entries: HashMap<String, Entry>,
-	model: Box<dyn Evictor + Send>,
-	capacity: usize
+	model: Box<dyn Evictor + Send + Sync>,
+	model_kind: Model,
+	capacity: usize,
+	last_write_at: Instant,
+	// only kept while retraining is on, nothing else reads them
+	observations: Option<Observations>,
+	revision: u64,
+	// only watched keys are tracked, each with how many sessions watch it, and kept apart from the entries so evicting a key cannot take its revision back
+	revisions: HashMap<String, (u64, usize)>,
+	keyspace: Arc<Keyspace>,
+	stats: Arc<Mutex<Stats>>
+}
+
+// values may hold secrets, so only keys and sizes ever reach the logs
+impl Debug for Cache {
+	fn fmt(self: &Self, formatter: &mut Formatter<'_>) -> _Result {
+		formatter.debug_struct("Cache")
+			.field("capacity", &self.capacity)
+			.field("len", &self.entries.len())
+			.field("model", &self.model_kind.to_string())
+			.field("keys", &self.entries.iter()
+				.map(|(key, entry): (&String, &Entry)| KeySize(key, entry.value.size()))
+				.collect::<Vec<KeySize>>())
+			.finish()
+	}
 }
 
 impl Cache {
-	pub fn new(model: Model, capacity: usize) -> Result<Cache> {
+	pub fn new(model: Model, capacity: usize, keyspace: Arc<Keyspace>, stats: Arc<Mutex<Stats>>) -> Result<Cache> {
 		info!("initializing cache with capacity of {}\n", capacity);
 
 		Ok(Cache {
@@ -56,19 +200,114 @@ impl Cache {
 				Model::LeastFrequentlyUsed => Box::new(LeastFrequentlyUsed::new()),
 				Model::LeastRecentlyUsed => Box::new(LeastRecentlyUsed::new())
 			},
-			capacity: capacity
+			model_kind: model,
+			capacity: capacity,
+			last_write_at: Instant::now(),
+			observations: if ARGUMENT.retrain_interval != 0 {
+				Some(Observations::new(ARGUMENT.retrain_lookback))
+			} else {
+				None
+			},
+			revision: 0,
+			revisions: HashMap::new(),
+			keyspace: keyspace,
+			stats: stats
 		})
 	}
 
-	pub fn set(self: &mut Self, key: &str, entry: Entry) -> Result<()> {
+	// promotions from storage are not writes, so callers mark client mutations themselves
+	pub fn mark_written(self: &mut Self) {
+		self.last_write_at = Instant::now();
+	}
+
+	pub fn last_write_at(self: &Self) -> Instant {
+		self.last_write_at
+	}
+
+	pub fn set_model(self: &mut Self, model: Box<dyn Evictor + Send + Sync>) {
+		self.model = model;
+	}
+
+	pub fn drain_observations(self: &mut Self, now: u64) -> Vec<Observation> {
+		if let Some(observations) = &mut self.observations {
+			observations.drain(now)
+		} else {
+			Vec::new()
+		}
+	}
+
+	// like mark_written, only client mutations are announced and evictions are the one exception
+	pub fn notify(self: &Self, key: &str, event: KeyspaceEvent) -> Result<()> {
+		self.keyspace.publish(key, event)
+	}
+
+	fn stats(self: &Self) -> Result<MutexGuard<'_, Stats>> {
+		Ok(self.stats.lock()
+			.map_err(|error: PoisonError<MutexGuard<'_, Stats>>| error.to_string())?)
+	}
+
+	pub fn len(self: &Self) -> usize {
+		self.entries.len()
+	}
+
+	pub fn capacity(self: &Self) -> usize {
+		self.capacity
+	}
+
+	fn bump(self: &mut Self, key: &str) {
+		self.revision += 1;
+
+		if let Some((revision, _)) = self.revisions.get_mut(key) {
+			*revision = self.revision;
+		}
+	}
+
+	// a key nobody watched yet starts at the current revision, so anything after this call moves it
+	pub fn watch(self: &mut Self, key: &str) -> u64 {
+		let revision: u64 = self.revision;
+		let (revision, watcher_count): &mut (u64, usize) = self.revisions.entry(key.to_owned())
+			.or_insert((revision, 0));
+
+		*watcher_count += 1;
+
+		*revision
+	}
+
+	pub fn unwatch(self: &mut Self, key: &str) {
+		if let Some((_, watcher_count)) = self.revisions.get_mut(key) {
+			*watcher_count -= 1;
+
+			if *watcher_count == 0 {
+				self.revisions.remove(key);
+			}
+		}
+	}
+
+	// promotions count too, so a watcher may see a change that was only an eviction and reload
+	pub fn revision(self: &Self, key: &str) -> u64 {
+		self.revisions.get(key)
+			.map(|(revision, _): &(u64, usize)| *revision)
+			.unwrap_or(0)
+	}
+
+	// a new value drops any previous lifetime unless it brings its own, given in milliseconds
+	pub fn set(self: &mut Self, key: &str, mut entry: Entry, ttl: Option<u64>) -> Result<()> {
 		let entries: String = if ARGUMENT.is_verbose {
-			format!("{:#?}", self.entries)
+			format!("{:#?}", self)
 		} else {
 			String::new()
 		};
 
+		self.bump(key);
+		entry.expires_at = if let Some(ttl) = ttl {
+			Some(unix_epoch_millis()?.saturating_add(ttl))
+		} else {
+			None
+		};
+
 		if let Some(old_entry) = self.entries.get_mut(key) {
 			old_entry.value = entry.value;
+			old_entry.expires_at = entry.expires_at;
 			old_entry.accessed_at = entry.accessed_at;
 			old_entry.access_count += entry.access_count;
 
@@ -76,13 +315,28 @@ impl Cache {
 				debug!("set {:?}:{:#?} to {}\n", key, old_entry, entries);
 			}
 		} else {
+			let now: u64 = unix_epoch()?;
+
+			if let Some(observations) = &mut self.observations {
+				observations.request(key, now);
+			}
+
 			if self.entries.len() == self.capacity {
 				let victim_key: String = self.model.select_victim(&self.entries)?;
 
+				if let Some(observations) = &mut self.observations {
+					if let Some(victim) = self.entries.get(&victim_key) {
+						observations.evict(&victim_key, features(victim, self.entries.capacity(), now), now);
+					}
+				}
+
 				if let Some(old_entry) = self.entries.remove(&victim_key) {
 					if ARGUMENT.is_verbose {
 						debug!("evicted {:?}:{:#?} and set {:?}:{:#?} to {}\n", victim_key, old_entry, key, entry, entries);
 					}
+
+					self.stats()?.evict();
+					self.keyspace.publish(&victim_key, KeyspaceEvent::Evict)?;
 				}
 			} else if ARGUMENT.is_verbose {
 				debug!("set {:?}:{:#?} to {}\n", key, entry, entries);
@@ -96,10 +350,26 @@ impl Cache {
 
 	pub fn get(self: &mut Self, key: &str) -> Result<Option<&Entry>> {
 		let entries: String = if ARGUMENT.is_verbose {
-			format!("{:#?}", self.entries)
+			format!("{:#?}", self)
 		} else {
 			String::new()
 		};
+		let now: u64 = unix_epoch_millis()?;
+
+		// an expired entry is dropped on sight, storage still has it for the next promotion
+		if self.entries.get(key)
+			.is_some_and(|entry: &Entry| entry.is_expired(now)) {
+			self.remove(key);
+			self.stats()?.miss();
+
+			return Ok(None);
+		}
+
+		if self.entries.contains_key(key) {
+			self.stats()?.hit();
+		} else {
+			self.stats()?.miss();
+		}
 
 		Ok(if let Some(entry) = self.entries.get_mut(key) {
 			entry.access_count += 1;
@@ -115,15 +385,143 @@ impl Cache {
 		})
 	}
 
-	pub fn remove(self: &mut Self, key: &str) -> bool {
-		if let Some(entry) = self.entries.remove(key) {
+	// metadata only, so the access bookkeeping the evictor relies on stays untouched
+	pub fn expire(self: &mut Self, key: &str, ttl: u64) -> Result<Option<u64>> {
+		let now: u64 = unix_epoch_millis()?;
+
+		Ok(match self.entries.get_mut(key) {
+			Some(entry) if !entry.is_expired(now) => {
+				entry.expires_at = Some(now.saturating_add(ttl));
+
+				if ARGUMENT.is_verbose {
+					debug!("expire {:?} in {}ms\n", key, ttl);
+				}
+
+				entry.expires_at
+			},
+			_ => None
+		})
+	}
+
+	// false when the key is absent or never expired in the first place
+	pub fn persist(self: &mut Self, key: &str) -> Result<bool> {
+		let now: u64 = unix_epoch_millis()?;
+
+		Ok(match self.entries.get_mut(key) {
+			Some(entry) if !entry.is_expired(now) && entry.expires_at.is_some() => {
+				entry.expires_at = None;
+
+				if ARGUMENT.is_verbose {
+					debug!("persist {:?}\n", key);
+				}
+
+				true
+			},
+			_ => false
+		})
+	}
+
+	// None for an absent key, Some(None) for one that never expires
+	pub fn expiry(self: &Self, key: &str) -> Result<Option<Option<u64>>> {
+		let now: u64 = unix_epoch_millis()?;
+
+		Ok(self.entries.get(key)
+			.filter(|entry: &&Entry| !entry.is_expired(now))
+			.map(|entry: &Entry| entry.expires_at))
+	}
+
+	pub fn remove_expired(self: &mut Self) -> Result<Vec<String>> {
+		let now: u64 = unix_epoch_millis()?;
+		let keys: Vec<String> = self.entries.iter()
+			.filter(|(_, entry): &(&String, &Entry)| entry.is_expired(now))
+			.map(|(key, _): (&String, &Entry)| key.clone())
+			.collect();
+
+		for key in &keys {
+			self.remove(key);
+		}
+
+		Ok(keys)
+	}
+
+	pub fn keys(self: &Self) -> impl Iterator<Item = &String> {
+		self.entries.keys()
+	}
+
+	pub fn contains(self: &Self, key: &str) -> Result<bool> {
+		let now: u64 = unix_epoch_millis()?;
+
+		Ok(self.entries.get(key)
+			.is_some_and(|entry: &Entry| !entry.is_expired(now)))
+	}
+
+	// only taken to modify the entry in place, so the revision moves up front
+	pub fn get_mut(self: &mut Self, key: &str) -> Result<Option<&mut Entry>> {
+		if self.entries.contains_key(key) {
+			self.bump(key);
+		}
+
+		Ok(if let Some(entry) = self.entries.get_mut(key) {
+			entry.access_count += 1;
+			entry.accessed_at = unix_epoch()?;
+
 			if ARGUMENT.is_verbose {
-				debug!("removed {:?}:{:#?} and became {:#?}\n", key, entry, self.entries);
+				debug!("modify {:?}\n", key);
 			}
 
-			true
+			Some(entry)
 		} else {
-			false
+			None
+		})
+	}
+
+	pub fn random_keys(self: &Self, count: usize) -> Vec<String> {
+		let keys: Vec<String> = random_sample(self.entries.keys().collect::<Vec<&String>>(), count, true)
+			.into_iter()
+			.cloned()
+			.collect();
+
+		if ARGUMENT.is_verbose {
+			debug!("sampled {:?} from {} entries\n", keys, self.entries.len());
+		}
+
+		keys
+	}
+
+	pub fn clear(self: &mut Self) {
+		self.entries = HashMap::with_capacity(self.capacity);
+		self.revision += 1;
+
+		// every watched key may be gone now, so all of them count as changed
+		for (revision, _) in self.revisions.values_mut() {
+			*revision = self.revision;
+		}
+
+		if ARGUMENT.is_verbose {
+			debug!("cleared and became {:#?}\n", self);
 		}
 	}
+
+	pub fn remove(self: &mut Self, key: &str) -> bool {
+		self.remove_returning(key)
+			.is_some()
+	}
+
+	// expired entries come back as well, the caller decides whether they still count
+	pub fn remove_returning(self: &mut Self, key: &str) -> Option<Entry> {
+		let entry: Option<Entry> = self.entries.remove(key);
+
+		// an evicted key may still be deleted from storage, and whoever watched it must notice
+		if entry.is_some() || self.revisions.contains_key(key) {
+			self.bump(key);
+		}
+
+		if ARGUMENT.is_verbose {
+			if let Some(entry) = &entry {
+				debug!("removed {:?}:{:#?} and became {:#?}\n", key, entry, self);
+			}
+		}
+
+		entry
+	}
 }
\ No newline at end of file
@@ -1,11 +1,20 @@
 use std::{
+	collections::hash_map::RandomState,
 	error::Error,
-	io::{stderr, stdout},
+	fs::{read_to_string, write},
+	hash::{BuildHasher, Hasher},
+	io::{ErrorKind, Read, Write, stderr, stdout},
 	net::TcpStream,
+	path::{Path, PathBuf},
 	process::exit,
 	result::Result as _Result,
 	sync::LazyLock,
-	time::{SystemTime, UNIX_EPOCH}
+	time::{Duration, Instant, SystemTime, UNIX_EPOCH}
+};
+#[cfg(unix)]
+use std::os::{
+	fd::OwnedFd,
+	unix::net::{SocketAddr as UnixSocketAddr, UnixStream}
 };
 use crate::{
 	argument::Argument,
@@ -14,6 +23,8 @@ use crate::{
 
 pub type Job = Box<dyn FnOnce() + Send + 'static>;
 
+pub type Task = (Instant, Job);
+
 pub type Result<T, E = Box<dyn Error>> = _Result<T, E>;
 
 pub const ARGUMENT: LazyLock<Argument> = LazyLock::new(|| {
@@ -75,14 +86,212 @@ pub fn unix_epoch() -> Result<u64> {
 	Ok(SystemTime::now().duration_since(UNIX_EPOCH)?.as_secs())
 }
 
+pub fn unix_epoch_millis() -> Result<u64> {
+	Ok(SystemTime::now().duration_since(UNIX_EPOCH)?.as_millis() as u64)
+}
+
+pub fn random() -> u64 {
+	RandomState::new().build_hasher().finish()
+}
+
+// resolves negative indices against len and clamps the end, None when no index is left in between
+pub fn normalize_range(start: i32, end: i32, len: usize) -> Option<(usize, usize)> {
+	let len: i64 = len as i64;
+	let start: i64 = if start < 0 {
+		(len + start as i64).max(0)
+	} else {
+		start as i64
+	};
+	let end: i64 = if end < 0 {
+		len + end as i64
+	} else {
+		(end as i64).min(len - 1)
+	};
+
+	if start > end || start >= len {
+		None
+	} else {
+		Some((start as usize, end as usize))
+	}
+}
+
+// distinct items come from reservoir sampling in a single pass, repeated ones from independent draws
+pub fn random_sample<T: Clone>(items: Vec<T>, count: usize, is_distinct: bool) -> Vec<T> {
+	if items.len() == 0 {
+		return Vec::new();
+	}
+
+	if !is_distinct {
+		return (0..count).map(|_: usize| items[(random() % items.len() as u64) as usize].clone())
+			.collect();
+	}
+
+	let mut sampled: Vec<T> = Vec::with_capacity(count.min(items.len()));
+
+	for (i, item) in items.into_iter().enumerate() {
+		if i < count {
+			sampled.push(item);
+		} else {
+			let j: usize = (random() % (i as u64 + 1)) as usize;
+
+			if j < count {
+				sampled[j] = item;
+			}
+		}
+	}
+
+	sampled
+}
+
+// only what JSON requires, anything else passes through as UTF-8
+pub fn escape_json(text: &str) -> String {
+	let mut escaped: String = String::with_capacity(text.len());
+
+	for character in text.chars() {
+		match character {
+			'"' => escaped.push_str("\\\""),
+			'\\' => escaped.push_str("\\\\"),
+			'\n' => escaped.push_str("\\n"),
+			'\r' => escaped.push_str("\\r"),
+			'\t' => escaped.push_str("\\t"),
+			character if (character as u32) < 0x20 => escaped.push_str(&format!("\\u{:04x}", character as u32)),
+			character => escaped.push(character)
+		}
+	}
+
+	escaped
+}
+
+// '*' matches any run of characters and '?' exactly one, backtracking only to the last '*'
+pub fn is_glob_match(pattern: &str, text: &str) -> bool {
+	let pattern: Vec<char> = pattern.chars().collect();
+	let text: Vec<char> = text.chars().collect();
+	let (mut i, mut j): (usize, usize) = (0, 0);
+	let mut star: Option<(usize, usize)> = None;
+
+	while j < text.len() {
+		if i < pattern.len() && (pattern[i] == '?' || pattern[i] == text[j]) {
+			i += 1;
+			j += 1;
+		} else if i < pattern.len() && pattern[i] == '*' {
+			star = Some((i, j));
+			i += 1;
+		} else if let Some((star_i, star_j)) = star {
+			i = star_i + 1;
+			j = star_j + 1;
+			star = Some((star_i, star_j + 1));
+		} else {
+			return false;
+		}
+	}
+
+	pattern[i..].iter().all(|character: &char| *character == '*')
+}
+
+// kept next to the data so the identity survives restarts
+pub const NODE_ID_FILE: &str = ".node-id";
+
+pub fn load_node_id(directory: &str) -> Result<String> {
+	let file: PathBuf = Path::new(directory).join(NODE_ID_FILE);
+
+	if file.exists() {
+		let node_id: String = read_to_string(&file)?.trim()
+			.to_owned();
+
+		if node_id.len() != 40 || !node_id.chars().all(|character: char| character.is_ascii_hexdigit()) {
+			return Err(Box::from("node id must be 40 hexadecimal characters"));
+		}
+
+		Ok(node_id)
+	} else {
+		let node_id: String = format!("{:016x}{:016x}{:08x}", random(), random(), random() as u32);
+
+		write(&file, &node_id)?;
+
+		Ok(node_id)
+	}
+}
+
 pub fn log1p(x: u64) -> f32 {
 	(x as f64).ln_1p() as f32
 }
 
-pub fn get_address(stream: &TcpStream) -> String {
-	if let Ok(address) = stream.peer_addr() {
-		address.to_string()
-	} else {
-		"unknown".to_owned()
+pub trait Stream: Read + Write + Send {
+	fn get_address(self: &Self) -> String;
+
+	fn set_timeout(self: &Self, timeout: Duration) -> Result<()>;
+
+	fn set_no_delay(self: &Self) -> Result<()>;
+
+	fn is_connected(self: &Self) -> bool;
+}
+
+// peeks without blocking, so pending bytes are left for the next read and only an orderly close or an error counts as gone
+fn is_peer_connected(stream: &TcpStream) -> bool {
+	if stream.set_nonblocking(true).is_err() {
+		return true;
+	}
+
+	let is_connected: bool = match stream.peek(&mut [0]) {
+		Ok(length) => length != 0,
+		Err(error) => error.kind() == ErrorKind::WouldBlock
+	};
+	let _ = stream.set_nonblocking(false);
+
+	is_connected
+}
+
+impl Stream for TcpStream {
+	fn get_address(self: &Self) -> String {
+		if let Ok(address) = self.peer_addr() {
+			address.to_string()
+		} else {
+			"unknown".to_owned()
+		}
+	}
+
+	fn set_timeout(self: &Self, timeout: Duration) -> Result<()> {
+		Ok(self.set_read_timeout(Some(timeout))?)
+	}
+
+	fn set_no_delay(self: &Self) -> Result<()> {
+		Ok(self.set_nodelay(true)?)
+	}
+
+	fn is_connected(self: &Self) -> bool {
+		is_peer_connected(self)
+	}
+}
+
+#[cfg(unix)]
+impl Stream for UnixStream {
+	fn get_address(self: &Self) -> String {
+		// clients of unix sockets are almost always unnamed
+		if let Some(path) = self.peer_addr()
+			.ok()
+			.as_ref()
+			.and_then(|address: &UnixSocketAddr| address.as_pathname()) {
+			path.display()
+				.to_string()
+		} else {
+			"local".to_owned()
+		}
+	}
+
+	fn set_timeout(self: &Self, timeout: Duration) -> Result<()> {
+		Ok(self.set_read_timeout(Some(timeout))?)
+	}
+
+	fn set_no_delay(self: &Self) -> Result<()> {
+		Ok(())
+	}
+
+	// peeking a unix socket is not stable yet, but the same recv works on a duplicate of its descriptor
+	fn is_connected(self: &Self) -> bool {
+		if let Ok(stream) = self.try_clone() {
+			is_peer_connected(&TcpStream::from(OwnedFd::from(stream)))
+		} else {
+			true
+		}
 	}
 }
\ No newline at end of file
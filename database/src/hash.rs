@@ -0,0 +1,145 @@
+use std::{collections::HashMap, mem::replace, str::from_utf8};
+use crate::{
+	common::{ARGUMENT, Result},
+	debug
+};
+
+fn read_string(bytes: &[u8], offset: &mut usize) -> Result<String> {
+	if *offset + 4 > bytes.len() {
+		return Err(Box::from("hash must not be truncated"));
+	}
+
+	let length: usize = u32::from_be_bytes([bytes[*offset], bytes[*offset + 1], bytes[*offset + 2], bytes[*offset + 3]]) as usize;
+	let end: usize = *offset + 4 + length;
+
+	if end > bytes.len() {
+		return Err(Box::from("hash must not be truncated"));
+	}
+
+	let string: String = from_utf8(&bytes[*offset + 4..end])?.to_owned();
+
+	*offset = end;
+
+	Ok(string)
+}
+
+// small hashes are scanned in insertion order, which beats hashing while they stay small
+#[derive(Clone)]
+pub enum Hash {
+	Listpack(Vec<(String, String)>),
+	HashTable(HashMap<String, String>)
+}
+
+impl Hash {
+	pub fn new() -> Hash {
+		Hash::Listpack(Vec::new())
+	}
+
+	pub fn from_bytes(bytes: &[u8]) -> Result<Hash> {
+		let mut hash: Hash = Hash::new();
+		let mut offset: usize = 0;
+
+		// inserting converts along the way, so only the first few fields are ever scanned for
+		while offset < bytes.len() {
+			let field: String = read_string(bytes, &mut offset)?;
+			let value: String = read_string(bytes, &mut offset)?;
+
+			hash.insert(field, value);
+		}
+
+		Ok(hash)
+	}
+
+	// one way only, like lists, so a hash hovering at a limit does not flap
+	fn convert(self: &mut Self) {
+		if let Hash::Listpack(pairs) = self {
+			if pairs.len() > ARGUMENT.hash_max_listpack_entries || pairs.iter().any(|(_, value): &(String, String)| value.len() > ARGUMENT.hash_max_listpack_value) {
+				if ARGUMENT.is_verbose {
+					debug!("converted hash of {} fields from listpack to hashtable\n", pairs.len());
+				}
+
+				*self = Hash::HashTable(pairs.drain(..).collect());
+			}
+		}
+	}
+
+	pub fn get(self: &Self, field: &str) -> Option<&String> {
+		match self {
+			Hash::Listpack(pairs) => pairs.iter()
+				.find(|(candidate, _): &&(String, String)| candidate == field)
+				.map(|(_, value): &(String, String)| value),
+			Hash::HashTable(hash) => hash.get(field)
+		}
+	}
+
+	pub fn insert(self: &mut Self, field: String, value: String) -> Option<String> {
+		let old_value: Option<String> = match self {
+			Hash::Listpack(pairs) => if let Some(pair) = pairs.iter_mut().find(|(candidate, _): &&mut (String, String)| *candidate == field) {
+				Some(replace(&mut pair.1, value))
+			} else {
+				pairs.push((field, value));
+
+				None
+			},
+			Hash::HashTable(hash) => hash.insert(field, value)
+		};
+
+		self.convert();
+
+		old_value
+	}
+
+	pub fn remove(self: &mut Self, field: &str) -> Option<String> {
+		match self {
+			Hash::Listpack(pairs) => pairs.iter()
+				.position(|(candidate, _): &(String, String)| candidate == field)
+				.map(|index: usize| pairs.remove(index).1),
+			Hash::HashTable(hash) => hash.remove(field)
+		}
+	}
+
+	pub fn pairs(self: &Self) -> Vec<(&String, &String)> {
+		match self {
+			Hash::Listpack(pairs) => pairs.iter()
+				.map(|(field, value): &(String, String)| (field, value))
+				.collect(),
+			Hash::HashTable(hash) => hash.iter()
+				.collect()
+		}
+	}
+
+	pub fn len(self: &Self) -> usize {
+		match self {
+			Hash::Listpack(pairs) => pairs.len(),
+			Hash::HashTable(hash) => hash.len()
+		}
+	}
+
+	pub fn size(self: &Self) -> usize {
+		self.pairs()
+			.iter()
+			.map(|(field, value): &(&String, &String)| field.len() + value.len())
+			.sum()
+	}
+
+	pub fn encoding(self: &Self) -> &'static str {
+		match self {
+			Hash::Listpack(_) => "listpack",
+			Hash::HashTable(_) => "hashtable"
+		}
+	}
+
+	// pairs are laid out as <length:u32> <field> <length:u32> <value>, whatever the encoding in memory
+	pub fn to_bytes(self: &Self) -> Vec<u8> {
+		let mut bytes: Vec<u8> = Vec::new();
+
+		for (field, value) in self.pairs() {
+			bytes.extend_from_slice(&(field.len() as u32).to_be_bytes());
+			bytes.extend_from_slice(field.as_bytes());
+			bytes.extend_from_slice(&(value.len() as u32).to_be_bytes());
+			bytes.extend_from_slice(value.as_bytes());
+		}
+
+		bytes
+	}
+}
\ No newline at end of file
@@ -0,0 +1,196 @@
+use std::{
+	collections::HashMap,
+	sync::{
+		Mutex,
+		MutexGuard,
+		PoisonError,
+		atomic::{AtomicU64, AtomicUsize, Ordering},
+		mpsc::{Receiver, Sender, channel}
+	}
+};
+use crate::{
+	common::{ARGUMENT, Result, escape_json, is_glob_match, unix_epoch},
+	debug
+};
+
+pub enum KeyspaceEvent {
+	Set,
+	Delete,
+	Expire(u64),
+	Persist,
+	Evict,
+	Expired
+}
+
+impl KeyspaceEvent {
+	fn to_json(self: &Self, key: &str, timestamp: u64) -> String {
+		let (operation, ttl): (&str, Option<u64>) = match self {
+			KeyspaceEvent::Set => ("set", None),
+			KeyspaceEvent::Delete => ("del", None),
+			KeyspaceEvent::Expire(ttl) => ("expire", Some(*ttl)),
+			KeyspaceEvent::Persist => ("persist", None),
+			KeyspaceEvent::Evict => ("evict", None),
+			KeyspaceEvent::Expired => ("expired", None)
+		};
+
+		if let Some(ttl) = ttl {
+			format!("{{\"op\":\"{}\",\"key\":\"{}\",\"ts\":{},\"ttl\":{}}}", operation, escape_json(key), timestamp, ttl)
+		} else {
+			format!("{{\"op\":\"{}\",\"key\":\"{}\",\"ts\":{}}}", operation, escape_json(key), timestamp)
+		}
+	}
+}
+
+struct Subscribers {
+	keys: HashMap<String, Vec<(u64, Sender<String>)>>,
+	patterns: Vec<(u64, String, Sender<String>)>
+}
+
+impl Subscribers {
+	fn len(self: &Self) -> usize {
+		self.keys.len() + self.patterns.len()
+	}
+}
+
+// dropped on every way out of a waiter, taking its senders along so they cannot pile up
+pub struct Subscription<'a> {
+	keyspace: &'a Keyspace,
+	id: u64,
+	keys: Vec<String>,
+	receiver: Receiver<String>
+}
+
+impl<'a> Subscription<'a> {
+	pub fn receiver(self: &Self) -> &Receiver<String> {
+		&self.receiver
+	}
+}
+
+impl<'a> Drop for Subscription<'a> {
+	fn drop(self: &mut Self) {
+		self.keyspace.unsubscribe(self.id, &self.keys);
+	}
+}
+
+pub struct Keyspace {
+	subscribers: Mutex<Subscribers>,
+	// read without the lock, so a server nobody subscribed to pays one atomic load per write
+	subscription_count: AtomicUsize,
+	next_id: AtomicU64
+}
+
+impl Keyspace {
+	pub fn new() -> Keyspace {
+		Keyspace {
+			subscribers: Mutex::new(Subscribers {
+				keys: HashMap::new(),
+				patterns: Vec::new()
+			}),
+			subscription_count: AtomicUsize::new(0),
+			next_id: AtomicU64::new(0)
+		}
+	}
+
+	pub fn subscribe(self: &Self, key: &str) -> Result<Subscription<'_>> {
+		self.subscribe_many(&[key.to_owned()])
+	}
+
+	// one receiver for all of the keys, so a waiter can block on whichever changes first
+	pub fn subscribe_many(self: &Self, keys: &[String]) -> Result<Subscription<'_>> {
+		let (sender, receiver): (Sender<String>, Receiver<String>) = channel::<String>();
+		let id: u64 = self.next_id.fetch_add(1, Ordering::Relaxed);
+		let mut subscribers: MutexGuard<'_, Subscribers> = self.subscribers.lock()
+			.map_err(|error: PoisonError<MutexGuard<'_, Subscribers>>| error.to_string())?;
+
+		for key in keys {
+			subscribers.keys
+				.entry(key.clone())
+				.or_default()
+				.push((id, sender.clone()));
+		}
+
+		self.subscription_count.store(subscribers.len(), Ordering::Relaxed);
+
+		Ok(Subscription {
+			keyspace: self,
+			id: id,
+			keys: keys.to_vec(),
+			receiver: receiver
+		})
+	}
+
+	pub fn subscribe_pattern(self: &Self, pattern: &str) -> Result<Subscription<'_>> {
+		let (sender, receiver): (Sender<String>, Receiver<String>) = channel::<String>();
+		let id: u64 = self.next_id.fetch_add(1, Ordering::Relaxed);
+		let mut subscribers: MutexGuard<'_, Subscribers> = self.subscribers.lock()
+			.map_err(|error: PoisonError<MutexGuard<'_, Subscribers>>| error.to_string())?;
+
+		subscribers.patterns
+			.push((id, pattern.to_owned(), sender));
+		self.subscription_count.store(subscribers.len(), Ordering::Relaxed);
+
+		Ok(Subscription {
+			keyspace: self,
+			id: id,
+			keys: Vec::new(),
+			receiver: receiver
+		})
+	}
+
+	// a poisoned lock still holds consistent lists, and a drop has no way to report an error anyway
+	fn unsubscribe(self: &Self, id: u64, keys: &[String]) {
+		let mut subscribers: MutexGuard<'_, Subscribers> = self.subscribers.lock()
+			.unwrap_or_else(PoisonError::into_inner);
+
+		for key in keys {
+			if let Some(senders) = subscribers.keys.get_mut(key) {
+				senders.retain(|(sender_id, _): &(u64, Sender<String>)| *sender_id != id);
+
+				if senders.len() == 0 {
+					subscribers.keys.remove(key);
+				}
+			}
+		}
+
+		subscribers.patterns
+			.retain(|(pattern_id, _, _): &(u64, String, Sender<String>)| *pattern_id != id);
+		self.subscription_count.store(subscribers.len(), Ordering::Relaxed);
+	}
+
+	pub fn publish(self: &Self, key: &str, event: KeyspaceEvent) -> Result<()> {
+		if self.subscription_count.load(Ordering::Relaxed) == 0 {
+			return Ok(());
+		}
+
+		let json: String = event.to_json(key, unix_epoch()?);
+		let mut subscribers: MutexGuard<'_, Subscribers> = self.subscribers.lock()
+			.map_err(|error: PoisonError<MutexGuard<'_, Subscribers>>| error.to_string())?;
+		let mut count: usize = 0;
+
+		if let Some(senders) = subscribers.keys.get_mut(key) {
+			count += senders.len();
+			senders.retain(|(_, sender): &(u64, Sender<String>)| sender.send(json.clone()).is_ok());
+
+			if senders.len() == 0 {
+				subscribers.keys.remove(key);
+			}
+		}
+
+		// every pattern is tested on every write, which is fine for the handful a server usually has
+		subscribers.patterns
+			.retain(|(_, pattern, sender): &(u64, String, Sender<String>)| if is_glob_match(pattern, key) {
+				count += 1;
+
+				sender.send(json.clone()).is_ok()
+			} else {
+				true
+			});
+		self.subscription_count.store(subscribers.len(), Ordering::Relaxed);
+
+		if ARGUMENT.is_verbose && count != 0 {
+			debug!("notified {} subscriber(s) of {}\n", count, json);
+		}
+
+		Ok(())
+	}
+}
\ No newline at end of file
@@ -0,0 +1,336 @@
+use std::{collections::VecDeque, str::from_utf8};
+use crate::common::{ARGUMENT, Result, normalize_range};
+
+// every element is framed as <length:u32> <element> <length:u32>, so either end can be walked without an index
+#[derive(Clone)]
+pub struct Listpack {
+	bytes: Vec<u8>,
+	len: usize
+}
+
+impl Listpack {
+	pub fn new() -> Listpack {
+		Listpack {
+			bytes: Vec::new(),
+			len: 0
+		}
+	}
+
+	fn frame(element: &str) -> Vec<u8> {
+		let length: [u8; 4] = (element.len() as u32).to_be_bytes();
+		let mut frame: Vec<u8> = Vec::with_capacity(element.len() + 8);
+
+		frame.extend_from_slice(&length);
+		frame.extend_from_slice(element.as_bytes());
+		frame.extend_from_slice(&length);
+
+		frame
+	}
+
+	pub fn from_bytes(bytes: Vec<u8>) -> Result<Listpack> {
+		let mut offset: usize = 0;
+		let mut len: usize = 0;
+
+		while offset < bytes.len() {
+			if offset + 4 > bytes.len() {
+				return Err(Box::from("listpack must not be truncated"));
+			}
+
+			let length: usize = u32::from_be_bytes([bytes[offset], bytes[offset + 1], bytes[offset + 2], bytes[offset + 3]]) as usize;
+			let end: usize = offset + 4 + length;
+
+			if end + 4 > bytes.len() || bytes[offset..offset + 4] != bytes[end..end + 4] {
+				return Err(Box::from("listpack must not be truncated"));
+			}
+
+			from_utf8(&bytes[offset + 4..end])?;
+
+			offset = end + 4;
+			len += 1;
+		}
+
+		Ok(Listpack {
+			bytes: bytes,
+			len: len
+		})
+	}
+
+	pub fn push_front(self: &mut Self, element: &str) {
+		self.bytes.splice(0..0, Listpack::frame(element));
+		self.len += 1;
+	}
+
+	pub fn push_back(self: &mut Self, element: &str) {
+		self.bytes.extend(Listpack::frame(element));
+		self.len += 1;
+	}
+
+	fn length_at(self: &Self, offset: usize) -> usize {
+		u32::from_be_bytes([self.bytes[offset], self.bytes[offset + 1], self.bytes[offset + 2], self.bytes[offset + 3]]) as usize
+	}
+
+	pub fn pop_front(self: &mut Self) -> Option<String> {
+		if self.len == 0 {
+			return None;
+		}
+
+		let length: usize = self.length_at(0);
+		let element: String = String::from_utf8_lossy(&self.bytes[4..4 + length]).into_owned();
+
+		self.bytes.drain(..length + 8);
+		self.len -= 1;
+
+		Some(element)
+	}
+
+	pub fn pop_back(self: &mut Self) -> Option<String> {
+		if self.len == 0 {
+			return None;
+		}
+
+		let end: usize = self.bytes.len() - 4;
+		let length: usize = self.length_at(end);
+		let element: String = String::from_utf8_lossy(&self.bytes[end - length..end]).into_owned();
+
+		self.bytes.truncate(end - length - 4);
+		self.len -= 1;
+
+		Some(element)
+	}
+
+	pub fn insert(self: &mut Self, pivot: &str, element: &str, is_after: bool) -> bool {
+		let mut offset: usize = 0;
+
+		while offset < self.bytes.len() {
+			let length: usize = self.length_at(offset);
+			let next: usize = offset + length + 8;
+
+			if &self.bytes[offset + 4..offset + 4 + length] == pivot.as_bytes() {
+				let at: usize = if is_after {
+					next
+				} else {
+					offset
+				};
+
+				self.bytes.splice(at..at, Listpack::frame(element));
+				self.len += 1;
+
+				return true;
+			}
+
+			offset = next;
+		}
+
+		false
+	}
+
+	// compared in place, so a scan allocates nothing
+	pub fn position(self: &Self, element: &str, rank: usize, max_scan: usize) -> Option<usize> {
+		let mut offset: usize = 0;
+		let mut index: usize = 0;
+		let mut seen: usize = 0;
+
+		while offset < self.bytes.len() && (max_scan == 0 || index < max_scan) {
+			let length: usize = self.length_at(offset);
+
+			if &self.bytes[offset + 4..offset + 4 + length] == element.as_bytes() {
+				seen += 1;
+
+				if seen == rank {
+					return Some(index);
+				}
+			}
+
+			offset += length + 8;
+			index += 1;
+		}
+
+		None
+	}
+
+	pub fn elements(self: &Self) -> Vec<String> {
+		let mut elements: Vec<String> = Vec::with_capacity(self.len);
+		let mut offset: usize = 0;
+
+		while offset < self.bytes.len() {
+			let length: usize = self.length_at(offset);
+
+			// elements only ever come from strings or a validated listpack
+			elements.push(String::from_utf8_lossy(&self.bytes[offset + 4..offset + 4 + length]).into_owned());
+			offset += length + 8;
+		}
+
+		elements
+	}
+
+	pub fn len(self: &Self) -> usize {
+		self.len
+	}
+
+	pub fn as_bytes(self: &Self) -> &[u8] {
+		&self.bytes
+	}
+}
+
+#[derive(Clone)]
+pub enum List {
+	Listpack(Listpack),
+	LinkedList(VecDeque<String>)
+}
+
+impl List {
+	pub fn new() -> List {
+		List::Listpack(Listpack::new())
+	}
+
+	pub fn from_bytes(bytes: Vec<u8>) -> Result<List> {
+		let mut list: List = List::Listpack(Listpack::from_bytes(bytes)?);
+
+		list.convert();
+
+		Ok(list)
+	}
+
+	// a listpack only grows into a linked list, never back, so a list hovering at the limit does not flap
+	fn convert(self: &mut Self) {
+		if let List::Listpack(listpack) = self {
+			if listpack.len() > ARGUMENT.list_max_listpack_entries {
+				*self = List::LinkedList(VecDeque::from(listpack.elements()));
+			}
+		}
+	}
+
+	pub fn push_front(self: &mut Self, element: String) {
+		match self {
+			List::Listpack(listpack) => listpack.push_front(&element),
+			List::LinkedList(elements) => elements.push_front(element)
+		}
+
+		self.convert();
+	}
+
+	pub fn push_back(self: &mut Self, element: String) {
+		match self {
+			List::Listpack(listpack) => listpack.push_back(&element),
+			List::LinkedList(elements) => elements.push_back(element)
+		}
+
+		self.convert();
+	}
+
+	pub fn pop_front(self: &mut Self) -> Option<String> {
+		match self {
+			List::Listpack(listpack) => listpack.pop_front(),
+			List::LinkedList(elements) => elements.pop_front()
+		}
+	}
+
+	pub fn pop_back(self: &mut Self) -> Option<String> {
+		match self {
+			List::Listpack(listpack) => listpack.pop_back(),
+			List::LinkedList(elements) => elements.pop_back()
+		}
+	}
+
+	// only the first occurrence of the pivot counts
+	pub fn insert(self: &mut Self, pivot: &str, element: String, is_after: bool) -> bool {
+		let is_inserted: bool = match self {
+			List::Listpack(listpack) => listpack.insert(pivot, &element, is_after),
+			List::LinkedList(elements) => if let Some(index) = elements.iter().position(|candidate: &String| candidate == pivot) {
+				elements.insert(if is_after {
+					index + 1
+				} else {
+					index
+				}, element);
+
+				true
+			} else {
+				false
+			}
+		};
+
+		self.convert();
+
+		is_inserted
+	}
+
+	// rank counts matches from 1, and a max_scan of 0 looks at the whole list
+	pub fn position(self: &Self, element: &str, rank: usize, max_scan: usize) -> Option<usize> {
+		match self {
+			List::Listpack(listpack) => listpack.position(element, rank, max_scan),
+			List::LinkedList(elements) => elements.iter()
+				.take(if max_scan == 0 {
+					elements.len()
+				} else {
+					max_scan
+				})
+				.enumerate()
+				.filter(|(_, candidate): &(usize, &String)| *candidate == element)
+				.nth(rank - 1)
+				.map(|(index, _): (usize, &String)| index)
+		}
+	}
+
+	// inclusive on both ends, negative indices count from the back
+	pub fn range(self: &Self, start: i32, end: i32) -> Vec<String> {
+		let (start, end): (usize, usize) = if let Some(range) = normalize_range(start, end, self.len()) {
+			range
+		} else {
+			return Vec::new();
+		};
+		let (skip, take): (usize, usize) = (start, end - start + 1);
+
+		match self {
+			List::Listpack(listpack) => listpack.elements()
+				.into_iter()
+				.skip(skip)
+				.take(take)
+				.collect(),
+			List::LinkedList(elements) => elements.iter()
+				.skip(skip)
+				.take(take)
+				.cloned()
+				.collect()
+		}
+	}
+
+	pub fn len(self: &Self) -> usize {
+		match self {
+			List::Listpack(listpack) => listpack.len(),
+			List::LinkedList(elements) => elements.len()
+		}
+	}
+
+	pub fn size(self: &Self) -> usize {
+		match self {
+			List::Listpack(listpack) => listpack.as_bytes().len(),
+			List::LinkedList(elements) => elements.iter()
+				.map(|element: &String| element.len())
+				.sum()
+		}
+	}
+
+	pub fn encoding(self: &Self) -> &'static str {
+		match self {
+			List::Listpack(_) => "listpack",
+			List::LinkedList(_) => "linkedlist"
+		}
+	}
+
+	// storage always holds the listpack form, whatever the encoding in memory
+	pub fn to_bytes(self: &Self) -> Vec<u8> {
+		match self {
+			List::Listpack(listpack) => listpack.as_bytes()
+				.to_vec(),
+			List::LinkedList(elements) => {
+				let mut listpack: Listpack = Listpack::new();
+
+				for element in elements {
+					listpack.push_back(element);
+				}
+
+				listpack.bytes
+			}
+		}
+	}
+}
\ No newline at end of file
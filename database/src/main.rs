@@ -4,8 +4,16 @@ use crate::protocol::serve;
 mod argument;
 mod cache;
 mod common;
+mod hash;
+mod keyspace;
+mod list;
 mod model;
 mod protocol;
+mod retrain;
+mod set;
+mod slow_log;
+mod sorted_set;
+mod stats;
 mod storage;
 mod thread_pool;
 mod logger;
@@ -1,14 +1,18 @@
 use ort::{
 	execution_providers::{ExecutionProvider, XNNPACKExecutionProvider},
 	session::{
-		InMemorySession,
 		Session,
 		SessionOutputs,
 		builder::{GraphOptimizationLevel, SessionBuilder}
 	},
 	value::Value
 };
-use std::{collections::HashMap, iter::zip};
+use std::{
+	collections::HashMap,
+	fmt::{Display, Formatter, Result as _Result},
+	iter::zip,
+	path::Path
+};
 use crate::{
 	cache::{Entry, Evictor},
 	common::{ARGUMENT, Result, log1p, unix_epoch},
@@ -23,12 +27,32 @@ pub enum Model {
 	LeastFrequentlyUsed
 }
 
-pub struct DeepQNetwork<'a> {
-	model: InMemorySession<'a>
+impl Display for Model {
+	fn fmt(self: &Self, formatter: &mut Formatter<'_>) -> _Result {
+		write!(formatter, "{}", match self {
+			Model::DeepQNetwork => "DQN",
+			Model::LeastRecentlyUsed => "LRU",
+			Model::LeastFrequentlyUsed => "LFU"
+		})
+	}
 }
 
-impl<'a> DeepQNetwork<'a> {
-	pub fn new() -> Result<Self>  {
+// the same four inputs the bundled model was trained on, all log1p scaled
+pub fn features(entry: &Entry, capacity: usize, now: u64) -> [f32; 4] {
+	[
+		log1p(now.saturating_sub(entry.accessed_at)),
+		log1p(entry.access_count),
+		log1p(entry.value.size() as u64),
+		log1p(capacity as u64)
+	]
+}
+
+pub struct DeepQNetwork {
+	model: Session
+}
+
+impl DeepQNetwork {
+	fn builder() -> Result<SessionBuilder> {
 		let mut session: SessionBuilder = Session::builder()?.with_optimization_level(GraphOptimizationLevel::Level3)?;
 
 		info!("initializing model using DeepQNetwork on {}\n", {
@@ -78,13 +102,23 @@ impl<'a> DeepQNetwork<'a> {
 			}
 		});
 
+		Ok(session)
+	}
+
+	pub fn new() -> Result<Self>  {
+		Ok(DeepQNetwork {
+			model: DeepQNetwork::builder()?.commit_from_memory(include_bytes!("../model.onnx"))?
+		})
+	}
+
+	pub fn from_file(path: &Path) -> Result<Self> {
 		Ok(DeepQNetwork {
-			model: session.commit_from_memory_directly(include_bytes!("../model.onnx"))?
+			model: DeepQNetwork::builder()?.commit_from_file(path)?
 		})
 	}
 }
 
-impl<'a> Evictor for DeepQNetwork<'a> {
+impl Evictor for DeepQNetwork {
 	fn select_victim(self: &mut Self, entries: &HashMap<String, Entry>) -> Result<String> {
 		let length: usize = entries.len();
 
@@ -94,14 +128,11 @@ impl<'a> Evictor for DeepQNetwork<'a> {
 
 		let mut keys: Vec<&String> = Vec::with_capacity(length);
 		let mut inputs: Vec<f32> = Vec::with_capacity(length * 4);
-		let capacity: f32 = log1p(entries.capacity() as u64);
+		let now: u64 = unix_epoch()?;
 
 		for entry in entries {
 			keys.push(entry.0);
-			inputs.push(log1p(unix_epoch()? - entry.1.accessed_at));
-			inputs.push(log1p(entry.1.access_count));
-			inputs.push(log1p(entry.1.value.len() as u64));
-			inputs.push(capacity);
+			inputs.extend(features(entry.1, entries.capacity(), now));
 		}
 
 		let output: SessionOutputs = self.model.run(vec![("args_0", Value::from_array((([length, 4]), inputs))?)])?;
@@ -2,8 +2,11 @@ use std::{
 	cmp::Ordering,
 	error::Error,
 	fmt::{Display, Formatter, Result as _Result},
-	io::{IoSlice, Read, Write, Error as _Error, ErrorKind},
-	net::{TcpListener, TcpStream},
+	collections::{BTreeSet, HashMap, HashSet},
+	io::{IoSlice, Read, Write, Error as _Error, ErrorKind, Result as IoResult},
+	iter::{once, zip},
+	net::TcpListener,
+	num::{ParseFloatError, ParseIntError},
 	sync::{
 		Arc,
 		Mutex,
@@ -11,16 +14,33 @@ use std::{
 		PoisonError,
 		RwLock,
 		RwLockReadGuard,
-		RwLockWriteGuard
+		RwLockWriteGuard,
+		atomic::{AtomicBool, AtomicU64, Ordering as AtomicOrdering},
+		mpsc::{RecvTimeoutError, SendError}
 	},
-	time::Duration
+	thread::{sleep, spawn, JoinHandle},
+	time::{Duration, Instant}
+};
+#[cfg(unix)]
+use std::{
+	fs::{metadata, remove_file},
+	os::unix::{fs::FileTypeExt, net::UnixListener}
 };
 use crate::{
+	argument::{Argument, Bind},
 	common::Result,
-	cache::{Cache, Entry},
-	common::{ARGUMENT, get_address},
+	cache::{Cache, Entry, Value},
+	hash::Hash,
+	common::{ARGUMENT, Stream, Task, is_glob_match, load_node_id, random, random_sample, unix_epoch_millis},
+	keyspace::{Keyspace, KeyspaceEvent, Subscription},
+	list::List,
+	retrain::retrain,
+	set::{SetOperation, combine, parse},
+	slow_log::SlowLog,
+	sorted_set::SortedSet,
+	stats::Stats,
 	storage::Storage,
-	thread_pool::ThreadPool,
+	thread_pool::{Telemetry, ThreadPool},
 	error,
 	info,
 	warn
@@ -32,17 +52,119 @@ use crate::{
 	-- handshake --
 	READY  <major:u8> <minor:u8> <patch:u8>
 	HELLO  <major:u8> <minor:u8> <patch:u8>
+	(every key takes a u16 length from HELLO 0.1.0 on, as do the keys of KEY_LIST, while fields, patterns and names keep u8;
+	 older clients keep u8 for keys too and are not sent KEY_LIST keys longer than 255 bytes)
 
 	-- request --
 	NOOP
-	SET    <length:u8> <key:String> <length:u32> <value:String>
-	DELETE <length:u8> <key:String>
-	GET    <length:u8> <key:String>
+	SET    <length:u8|u16> <key:String> <length:u32> <value:String>
+	DELETE <length:u8|u16> <key:String>
+	GET    <length:u8|u16> <key:String>
+	SRANDMEMBER <count:u32>
+	SLOWLOG_GET <count:u32>
+	SLOWLOG_LEN
+	SLOWLOG_RESET
+	STATS
+	SINTERSTORE <length:u8|u16> <destination:String> <length:u8|u16> <key:String> <length:u8|u16> <key:String>
+	SUNIONSTORE <length:u8|u16> <destination:String> <length:u8|u16> <key:String> <length:u8|u16> <key:String>
+	SDIFFSTORE  <length:u8|u16> <destination:String> <length:u8|u16> <key:String> <length:u8|u16> <key:String>
+	SCARD  <length:u8|u16> <key:String>
+	CONFIG_VALIDATE <length:u8> <name:String> <length:u32> <value:String>
+	CLUSTER_MYID
+	COMMAND_COUNT  (counts every opcode STATS reports on, HELLO and QUIT included)
+	SUBSCRIBE_STATS <interval:u32>
+	UNSUBSCRIBE_STATS
+	WAIT_QUIET <period:u32> <timeout:u32>
+	WATCH  <length:u8|u16> <key:String>
+	EXEC_IF_UNCHANGED <length:u8|u16> <key:String> <length:u32> <operation:u8> <arguments>
+	GETDEL_MANY <count:u8> (<length:u8|u16> <key:String>)*
+	RANDOMKEY
+	OBJECT_ENCODING <length:u8|u16> <key:String>
+	LPUSH  <length:u8|u16> <key:String> <length:u32> <element:String>
+	RPUSH  <length:u8|u16> <key:String> <length:u32> <element:String>
+	LPOP   <length:u8|u16> <key:String>
+	RPOP   <length:u8|u16> <key:String>
+	LLEN   <length:u8|u16> <key:String>
+	LRANGE <length:u8|u16> <key:String> <start:i32> <end:i32>
+	LINSERT <length:u8|u16> <key:String> <position:u8> <length:u32> <pivot:String> <length:u32> <element:String>
+	MGET   <count:u8> (<length:u8|u16> <key:String>)*
+	HSET   <length:u8|u16> <key:String> <length:u8> <field:String> <length:u32> <value:String>
+	HGET   <length:u8|u16> <key:String> <length:u8> <field:String>
+	HDEL   <length:u8|u16> <key:String> <length:u8> <field:String>
+	HGETALL <length:u8|u16> <key:String>
+	HMSET  <length:u8|u16> <key:String> <count:u8> (<length:u8> <field:String> <length:u32> <value:String>)*
+	HMGET  <length:u8|u16> <key:String> <count:u8> (<length:u8> <field:String>)*
+	MSET   <count:u8> (<length:u8|u16> <key:String> <length:u32> <value:String>)*
+	MDEL   <count:u8> (<length:u8|u16> <key:String>)*  (answered with OKAY <count:u8>)
+	HKEYS  <length:u8|u16> <key:String>
+	HVALS  <length:u8|u16> <key:String>
+	HLEN   <length:u8|u16> <key:String>
+	HEXISTS <length:u8|u16> <key:String> <length:u8> <field:String>
+	EXISTS <length:u8|u16> <key:String>
+	HINCRBY <length:u8|u16> <key:String> <length:u8> <field:String> <delta:i64>
+	HINCRBYFLOAT <length:u8|u16> <key:String> <length:u8> <field:String> <delta:f64>
+	RENAME <length:u8|u16> <source:String> <length:u8|u16> <destination:String>
+	ZADD   <length:u8|u16> <key:String> <score:f64> <length:u32> <member:String>
+	ZRANGE <length:u8|u16> <key:String> <start:i32> <end:i32>
+	ZRANK  <length:u8|u16> <key:String> <length:u32> <member:String>
+	ZSCORE <length:u8|u16> <key:String> <length:u32> <member:String>
+	KEYS   <length:u8> <pattern:String>
+	ZRANGEBYSCORE <length:u8|u16> <key:String> <minimum:f64> <maximum:f64> <limit:u32>
+	ZRANGEBYLEX <length:u8|u16> <key:String> <length:u8> <minimum:String> <length:u8> <maximum:String>
+	SCAN   <cursor:u32> <count:u8>
+	ZREM   <length:u8|u16> <key:String> <count:u8> (<length:u32> <member:String>)*
+	ZPOPMIN <length:u8|u16> <key:String> <count:u8>
+	ZPOPMAX <length:u8|u16> <key:String> <count:u8>
+	ZUNIONSTORE <length:u8|u16> <destination:String> <count:u8> (<length:u8|u16> <key:String>)* <count:u8> (<weight:f64>)*
+	ZINTERSTORE <length:u8|u16> <destination:String> <count:u8> (<length:u8|u16> <key:String>)* <count:u8> (<weight:f64>)*
+	EXPIRE <length:u8|u16> <key:String> <ttl:u32>
+	CONFIG_SET <length:u8> <name:String> <length:u32> <value:String>
+	TTL    <length:u8|u16> <key:String>
+	COMMAND_GETKEYS <operation:u8> <length:u32> <arguments>
+	PERSIST <length:u8|u16> <key:String>
+	PEXPIRE <length:u8|u16> <key:String> <ttl:u64>
+	PTTL   <length:u8|u16> <key:String>
+	SUBSCRIBE_KEYSPACE <length:u8|u16> <key:String>
+	UNSUBSCRIBE_KEYSPACE
+	SUBSCRIBE_KEYSPACE_PATTERN <length:u8> <pattern:String>
+	FLUSH
+	GETEX_PERSIST <length:u8|u16> <key:String>
+	SETNX  <length:u8|u16> <key:String> <length:u32> <value:String>  (answered with CREATED once written)
+	CLIENT_SETNAME <length:u8> <name:String>
+	CLIENT_GETNAME
+	GETSET <length:u8|u16> <key:String> <length:u32> <value:String>  (answered with OKAY for a new key)
+	LPOS   <length:u8|u16> <key:String> <length:u32> <element:String> <flags:u8> [<rank:u8>] [<max_scan:u32>]
+	GETDEL <length:u8|u16> <key:String>
+	INCR   <length:u8|u16> <key:String>
+	DECR   <length:u8|u16> <key:String>
+	INCRBY <length:u8|u16> <key:String> <delta:i64>
+	DECRBY <length:u8|u16> <key:String> <delta:i64>
+	GETSET_EX <length:u8|u16> <key:String> <length:u32> <value:String> <ttl:u32>  (a ttl of 0 means none)
+	APPEND <length:u8|u16> <key:String> <length:u32> <suffix:String>
+	STRLEN <length:u8|u16> <key:String>
+	HRANDFIELD <length:u8|u16> <key:String> <count:i8> <with_values:u8>
+	PING   <length:u32> <payload>  (a length of 0 sends no payload)
+	ZRANDMEMBER <length:u8|u16> <key:String> <count:i8> <with_scores:u8>
+	COPY   <length:u8|u16> <source:String> <length:u8|u16> <destination:String> <flags:u8>  (bit 0 replaces an existing destination)
+	ZDIFF <count:u8> (<length:u8|u16> <key:String>)*
+	ZDIFFSTORE <length:u8|u16> <destination:String> <count:u8> (<length:u8|u16> <key:String>)*
+	INFO
+	WAIT_FOR_KEY <length:u8|u16> <key:String> <timeout:u32>
+	BLPOP <count:u8> (<length:u8|u16> <key:String>)* <timeout:u32>  (a timeout of 0 waits forever)
+	BRPOP <count:u8> (<length:u8|u16> <key:String>)* <timeout:u32>  (a timeout of 0 waits forever)
 
 	-- responses --
 	OKAY
 	VALUE  <length:u32> <value:String>
 	ERROR  <length:u32> <message:String>
+	VALUES <count:u32> (<length:u32> <value:String>)*
+	NOT_FOUND
+	KEY_LIST <count:u16> (<length:u8|u16> <key:String>)*
+	DURATION <seconds:u32>
+	DURATION_MILLIS <milliseconds:u64>
+	KEYSPACE_EVENT <length:u32> <event:String>  (JSON {"op", "key", "ts"} plus "ttl" in milliseconds for expire)
+	CREATED
+	PONG   <length:u32> <payload>
 
 	-- termination --
 	QUIT
@@ -54,20 +176,284 @@ pub const OPERATION_NOOP: &[u8; 1] = &[0b00000010];
 pub const OPERATION_SET: &[u8; 1] = &[0b00000011];
 pub const OPERATION_DELETE: &[u8; 1] = &[0b00000100];
 pub const OPERATION_GET: &[u8; 1] = &[0b00000101];
+pub const OPERATION_SRANDMEMBER: &[u8; 1] = &[0b00000110];
+pub const OPERATION_SLOWLOG_GET: &[u8; 1] = &[0b00000111];
+pub const OPERATION_SLOWLOG_LEN: &[u8; 1] = &[0b00001000];
+pub const OPERATION_SLOWLOG_RESET: &[u8; 1] = &[0b00001001];
+pub const OPERATION_STATS: &[u8; 1] = &[0b00001010];
+pub const OPERATION_SINTERSTORE: &[u8; 1] = &[0b00001011];
+pub const OPERATION_SUNIONSTORE: &[u8; 1] = &[0b00001100];
+pub const OPERATION_SDIFFSTORE: &[u8; 1] = &[0b00001101];
+pub const OPERATION_SCARD: &[u8; 1] = &[0b00001110];
+pub const OPERATION_CONFIG_VALIDATE: &[u8; 1] = &[0b00001111];
+pub const OPERATION_CLUSTER_MYID: &[u8; 1] = &[0b00010000];
+pub const OPERATION_COMMAND_COUNT: &[u8; 1] = &[0b00010001];
+pub const OPERATION_SUBSCRIBE_STATS: &[u8; 1] = &[0b00010010];
+pub const OPERATION_UNSUBSCRIBE_STATS: &[u8; 1] = &[0b00010011];
+pub const OPERATION_WAIT_QUIET: &[u8; 1] = &[0b00010100];
+pub const OPERATION_WATCH: &[u8; 1] = &[0b00010101];
+pub const OPERATION_EXEC_IF_UNCHANGED: &[u8; 1] = &[0b00010110];
+pub const OPERATION_GETDEL_MANY: &[u8; 1] = &[0b00010111];
+pub const OPERATION_RANDOMKEY: &[u8; 1] = &[0b00011000];
+pub const OPERATION_OBJECT_ENCODING: &[u8; 1] = &[0b00011001];
+pub const OPERATION_LPUSH: &[u8; 1] = &[0b00011010];
+pub const OPERATION_RPUSH: &[u8; 1] = &[0b00011011];
+pub const OPERATION_LPOP: &[u8; 1] = &[0b00011100];
+pub const OPERATION_RPOP: &[u8; 1] = &[0b00011101];
+pub const OPERATION_LLEN: &[u8; 1] = &[0b00011110];
+pub const OPERATION_LRANGE: &[u8; 1] = &[0b00011111];
+pub const OPERATION_LINSERT: &[u8; 1] = &[0b00100000];
+pub const OPERATION_MGET: &[u8; 1] = &[0b00100001];
+pub const OPERATION_HSET: &[u8; 1] = &[0b00100010];
+pub const OPERATION_HGET: &[u8; 1] = &[0b00100011];
+pub const OPERATION_HDEL: &[u8; 1] = &[0b00100100];
+pub const OPERATION_HGETALL: &[u8; 1] = &[0b00100101];
+pub const OPERATION_HMSET: &[u8; 1] = &[0b00100110];
+pub const OPERATION_HMGET: &[u8; 1] = &[0b00100111];
+pub const OPERATION_MSET: &[u8; 1] = &[0b00101000];
+pub const OPERATION_MDEL: &[u8; 1] = &[0b00101001];
+pub const OPERATION_HKEYS: &[u8; 1] = &[0b00101010];
+pub const OPERATION_HVALS: &[u8; 1] = &[0b00101011];
+pub const OPERATION_HLEN: &[u8; 1] = &[0b00101100];
+pub const OPERATION_HEXISTS: &[u8; 1] = &[0b00101101];
+pub const OPERATION_EXISTS: &[u8; 1] = &[0b00101110];
+pub const OPERATION_HINCRBY: &[u8; 1] = &[0b00101111];
+pub const OPERATION_HINCRBYFLOAT: &[u8; 1] = &[0b00110000];
+pub const OPERATION_RENAME: &[u8; 1] = &[0b00110001];
+pub const OPERATION_ZADD: &[u8; 1] = &[0b00110010];
+pub const OPERATION_ZRANGE: &[u8; 1] = &[0b00110011];
+pub const OPERATION_ZRANK: &[u8; 1] = &[0b00110100];
+pub const OPERATION_ZSCORE: &[u8; 1] = &[0b00110101];
+pub const OPERATION_KEYS: &[u8; 1] = &[0b00110110];
+pub const OPERATION_ZRANGEBYSCORE: &[u8; 1] = &[0b00110111];
+pub const OPERATION_ZRANGEBYLEX: &[u8; 1] = &[0b00111000];
+pub const OPERATION_SCAN: &[u8; 1] = &[0b00111001];
+pub const OPERATION_ZREM: &[u8; 1] = &[0b00111010];
+pub const OPERATION_ZPOPMIN: &[u8; 1] = &[0b00111011];
+pub const OPERATION_ZPOPMAX: &[u8; 1] = &[0b00111100];
+pub const OPERATION_ZUNIONSTORE: &[u8; 1] = &[0b00111101];
+pub const OPERATION_ZINTERSTORE: &[u8; 1] = &[0b00111110];
+pub const OPERATION_EXPIRE: &[u8; 1] = &[0b00111111];
+pub const OPERATION_CONFIG_SET: &[u8; 1] = &[0b01000000];
+pub const OPERATION_TTL: &[u8; 1] = &[0b01000001];
+pub const OPERATION_COMMAND_GETKEYS: &[u8; 1] = &[0b01000010];
+pub const OPERATION_PERSIST: &[u8; 1] = &[0b01000011];
+pub const OPERATION_PEXPIRE: &[u8; 1] = &[0b01000100];
+pub const OPERATION_PTTL: &[u8; 1] = &[0b01000101];
+pub const OPERATION_SUBSCRIBE_KEYSPACE: &[u8; 1] = &[0b01000110];
+pub const OPERATION_UNSUBSCRIBE_KEYSPACE: &[u8; 1] = &[0b01000111];
+pub const OPERATION_SUBSCRIBE_KEYSPACE_PATTERN: &[u8; 1] = &[0b01001000];
+pub const OPERATION_FLUSH: &[u8; 1] = &[0b01001001];
+pub const OPERATION_GETEX_PERSIST: &[u8; 1] = &[0b01001010];
+pub const OPERATION_SETNX: &[u8; 1] = &[0b01001011];
+pub const OPERATION_CLIENT_SETNAME: &[u8; 1] = &[0b01001100];
+pub const OPERATION_CLIENT_GETNAME: &[u8; 1] = &[0b01001101];
+pub const OPERATION_GETSET: &[u8; 1] = &[0b01001110];
+pub const OPERATION_LPOS: &[u8; 1] = &[0b01001111];
+pub const OPERATION_GETDEL: &[u8; 1] = &[0b01010000];
+pub const OPERATION_INCR: &[u8; 1] = &[0b01010001];
+pub const OPERATION_DECR: &[u8; 1] = &[0b01010010];
+pub const OPERATION_INCRBY: &[u8; 1] = &[0b01010011];
+pub const OPERATION_DECRBY: &[u8; 1] = &[0b01010100];
+pub const OPERATION_GETSET_EX: &[u8; 1] = &[0b01010101];
+pub const OPERATION_APPEND: &[u8; 1] = &[0b01010110];
+pub const OPERATION_STRLEN: &[u8; 1] = &[0b01010111];
+pub const OPERATION_HRANDFIELD: &[u8; 1] = &[0b01011000];
+pub const OPERATION_PING: &[u8; 1] = &[0b01011001];
+pub const OPERATION_ZRANDMEMBER: &[u8; 1] = &[0b01011010];
+pub const OPERATION_COPY: &[u8; 1] = &[0b01011011];
+pub const OPERATION_ZDIFF: &[u8; 1] = &[0b01011100];
+pub const OPERATION_ZDIFFSTORE: &[u8; 1] = &[0b01011101];
+pub const OPERATION_INFO: &[u8; 1] = &[0b01011110];
+pub const OPERATION_WAIT_FOR_KEY: &[u8; 1] = &[0b01011111];
+pub const OPERATION_BLPOP: &[u8; 1] = &[0b01100000];
+pub const OPERATION_BRPOP: &[u8; 1] = &[0b01100001];
 pub const OPERATION_OK: &[u8; 1] = &[0b10000010];
 pub const OPERATION_VALUE: &[u8; 1] = &[0b10000011];
 pub const OPERATION_ERROR: &[u8; 1] = &[0b10000100];
+pub const OPERATION_VALUES: &[u8; 1] = &[0b10000101];
+pub const OPERATION_NOT_FOUND: &[u8; 1] = &[0b10000110];
+pub const OPERATION_KEY_LIST: &[u8; 1] = &[0b10000111];
+pub const OPERATION_DURATION: &[u8; 1] = &[0b10001000];
+pub const OPERATION_DURATION_MILLIS: &[u8; 1] = &[0b10001001];
+pub const OPERATION_KEYSPACE_EVENT: &[u8; 1] = &[0b10001010];
+pub const OPERATION_CREATED: &[u8; 1] = &[0b10001011];
+pub const OPERATION_PONG: &[u8; 1] = &[0b10001100];
 pub const OPERATION_QUIT: &[u8; 1] = &[0b11111111];
 
-pub fn read_string<const N: usize>(stream: &mut TcpStream, byte_or_double_word: &mut [u8; N]) -> Result<String> {
+// every operation a client may send, in opcode order
+pub const OPERATIONS: &[(&[u8; 1], &str)] = &[
+	(OPERATION_HELLO, "HELLO"),
+	(OPERATION_NOOP, "NOOP"),
+	(OPERATION_SET, "SET"),
+	(OPERATION_DELETE, "DELETE"),
+	(OPERATION_GET, "GET"),
+	(OPERATION_SRANDMEMBER, "SRANDMEMBER"),
+	(OPERATION_SLOWLOG_GET, "SLOWLOG_GET"),
+	(OPERATION_SLOWLOG_LEN, "SLOWLOG_LEN"),
+	(OPERATION_SLOWLOG_RESET, "SLOWLOG_RESET"),
+	(OPERATION_STATS, "STATS"),
+	(OPERATION_SINTERSTORE, "SINTERSTORE"),
+	(OPERATION_SUNIONSTORE, "SUNIONSTORE"),
+	(OPERATION_SDIFFSTORE, "SDIFFSTORE"),
+	(OPERATION_SCARD, "SCARD"),
+	(OPERATION_CONFIG_VALIDATE, "CONFIG_VALIDATE"),
+	(OPERATION_CLUSTER_MYID, "CLUSTER_MYID"),
+	(OPERATION_COMMAND_COUNT, "COMMAND_COUNT"),
+	(OPERATION_SUBSCRIBE_STATS, "SUBSCRIBE_STATS"),
+	(OPERATION_UNSUBSCRIBE_STATS, "UNSUBSCRIBE_STATS"),
+	(OPERATION_WAIT_QUIET, "WAIT_QUIET"),
+	(OPERATION_WATCH, "WATCH"),
+	(OPERATION_EXEC_IF_UNCHANGED, "EXEC_IF_UNCHANGED"),
+	(OPERATION_GETDEL_MANY, "GETDEL_MANY"),
+	(OPERATION_RANDOMKEY, "RANDOMKEY"),
+	(OPERATION_OBJECT_ENCODING, "OBJECT_ENCODING"),
+	(OPERATION_LPUSH, "LPUSH"),
+	(OPERATION_RPUSH, "RPUSH"),
+	(OPERATION_LPOP, "LPOP"),
+	(OPERATION_RPOP, "RPOP"),
+	(OPERATION_LLEN, "LLEN"),
+	(OPERATION_LRANGE, "LRANGE"),
+	(OPERATION_LINSERT, "LINSERT"),
+	(OPERATION_MGET, "MGET"),
+	(OPERATION_HSET, "HSET"),
+	(OPERATION_HGET, "HGET"),
+	(OPERATION_HDEL, "HDEL"),
+	(OPERATION_HGETALL, "HGETALL"),
+	(OPERATION_HMSET, "HMSET"),
+	(OPERATION_HMGET, "HMGET"),
+	(OPERATION_MSET, "MSET"),
+	(OPERATION_MDEL, "MDEL"),
+	(OPERATION_HKEYS, "HKEYS"),
+	(OPERATION_HVALS, "HVALS"),
+	(OPERATION_HLEN, "HLEN"),
+	(OPERATION_HEXISTS, "HEXISTS"),
+	(OPERATION_EXISTS, "EXISTS"),
+	(OPERATION_HINCRBY, "HINCRBY"),
+	(OPERATION_HINCRBYFLOAT, "HINCRBYFLOAT"),
+	(OPERATION_RENAME, "RENAME"),
+	(OPERATION_ZADD, "ZADD"),
+	(OPERATION_ZRANGE, "ZRANGE"),
+	(OPERATION_ZRANK, "ZRANK"),
+	(OPERATION_ZSCORE, "ZSCORE"),
+	(OPERATION_KEYS, "KEYS"),
+	(OPERATION_ZRANGEBYSCORE, "ZRANGEBYSCORE"),
+	(OPERATION_ZRANGEBYLEX, "ZRANGEBYLEX"),
+	(OPERATION_SCAN, "SCAN"),
+	(OPERATION_ZREM, "ZREM"),
+	(OPERATION_ZPOPMIN, "ZPOPMIN"),
+	(OPERATION_ZPOPMAX, "ZPOPMAX"),
+	(OPERATION_ZUNIONSTORE, "ZUNIONSTORE"),
+	(OPERATION_ZINTERSTORE, "ZINTERSTORE"),
+	(OPERATION_EXPIRE, "EXPIRE"),
+	(OPERATION_CONFIG_SET, "CONFIG_SET"),
+	(OPERATION_TTL, "TTL"),
+	(OPERATION_COMMAND_GETKEYS, "COMMAND_GETKEYS"),
+	(OPERATION_PERSIST, "PERSIST"),
+	(OPERATION_PEXPIRE, "PEXPIRE"),
+	(OPERATION_PTTL, "PTTL"),
+	(OPERATION_SUBSCRIBE_KEYSPACE, "SUBSCRIBE_KEYSPACE"),
+	(OPERATION_UNSUBSCRIBE_KEYSPACE, "UNSUBSCRIBE_KEYSPACE"),
+	(OPERATION_SUBSCRIBE_KEYSPACE_PATTERN, "SUBSCRIBE_KEYSPACE_PATTERN"),
+	(OPERATION_FLUSH, "FLUSH"),
+	(OPERATION_GETEX_PERSIST, "GETEX_PERSIST"),
+	(OPERATION_SETNX, "SETNX"),
+	(OPERATION_CLIENT_SETNAME, "CLIENT_SETNAME"),
+	(OPERATION_CLIENT_GETNAME, "CLIENT_GETNAME"),
+	(OPERATION_GETSET, "GETSET"),
+	(OPERATION_LPOS, "LPOS"),
+	(OPERATION_GETDEL, "GETDEL"),
+	(OPERATION_INCR, "INCR"),
+	(OPERATION_DECR, "DECR"),
+	(OPERATION_INCRBY, "INCRBY"),
+	(OPERATION_DECRBY, "DECRBY"),
+	(OPERATION_GETSET_EX, "GETSET_EX"),
+	(OPERATION_APPEND, "APPEND"),
+	(OPERATION_STRLEN, "STRLEN"),
+	(OPERATION_HRANDFIELD, "HRANDFIELD"),
+	(OPERATION_PING, "PING"),
+	(OPERATION_ZRANDMEMBER, "ZRANDMEMBER"),
+	(OPERATION_COPY, "COPY"),
+	(OPERATION_ZDIFF, "ZDIFF"),
+	(OPERATION_ZDIFFSTORE, "ZDIFFSTORE"),
+	(OPERATION_INFO, "INFO"),
+	(OPERATION_WAIT_FOR_KEY, "WAIT_FOR_KEY"),
+	(OPERATION_BLPOP, "BLPOP"),
+	(OPERATION_BRPOP, "BRPOP"),
+	(OPERATION_QUIT, "QUIT")
+];
+
+// run without the shared gate: the blocking ones would starve EXEC_IF_UNCHANGED, which takes it itself
+const UNGATED_OPERATIONS: &[&[u8; 1]] = &[
+	OPERATION_SUBSCRIBE_STATS,
+	OPERATION_WAIT_QUIET,
+	OPERATION_EXEC_IF_UNCHANGED,
+	OPERATION_SUBSCRIBE_KEYSPACE,
+	OPERATION_SUBSCRIBE_KEYSPACE_PATTERN,
+	OPERATION_WAIT_FOR_KEY,
+	OPERATION_BLPOP,
+	OPERATION_BRPOP
+];
+
+// indexed by opcode byte, so counting is a single atomic add on the hot path
+static OPERATION_COUNTS: [AtomicU64; 256] = [const { AtomicU64::new(0) }; 256];
+
+pub fn operation_name(operation: u8) -> &'static str {
+	for (code, name) in OPERATIONS {
+		if code[0] == operation {
+			return name;
+		}
+	}
+
+	"UNKNOWN"
+}
+
+pub fn get_stats(telemetry: &Telemetry) -> String {
+	let (mean_wait_time, p99_wait_time): (u64, u64) = telemetry.wait_times();
+
+	let mut stats: String = format!("{{\"queue_depth\":{},\"mean_wait_us\":{},\"p99_wait_us\":{}", telemetry.queue_depth(), mean_wait_time, p99_wait_time);
+
+	for (code, name) in OPERATIONS {
+		stats.push_str(&format!(",\"{}_count\":{}", name.to_ascii_lowercase(), OPERATION_COUNTS[code[0] as usize].load(AtomicOrdering::Relaxed)));
+	}
+
+	stats.push('}');
+
+	stats
+}
+
+pub fn read_double_word(stream: &mut (impl Read + ?Sized), double_word: &mut [u8; 4]) -> Result<u32> {
+	stream.read_exact(double_word)?;
+
+	Ok(u32::from_be_bytes(*double_word))
+}
+
+pub fn read_quad_word(stream: &mut (impl Read + ?Sized), quad_word: &mut [u8; 8]) -> Result<u64> {
+	stream.read_exact(quad_word)?;
+
+	Ok(u64::from_be_bytes(*quad_word))
+}
+
+// a key of any operation, whose length field is one or two bytes depending on the client version
+pub fn read_key(stream: &mut (impl Read + ?Sized), byte: &mut [u8; 1], is_key_size_16: bool) -> Result<String> {
+	if is_key_size_16 {
+		read_string::<2>(stream, &mut [0; 2])
+	} else {
+		read_string::<1>(stream, byte)
+	}
+}
+
+pub fn read_string<const N: usize>(stream: &mut (impl Read + ?Sized), byte_or_double_word: &mut [u8; N]) -> Result<String> {
 	stream.read_exact(byte_or_double_word)?;
 
 	let mut buffer: Vec<u8> = vec![0; if N == 1 {
 		byte_or_double_word[0] as usize
+	} else if N == 2 {
+		(byte_or_double_word[0] as usize) << 8 | byte_or_double_word[1] as usize
 	} else if N == 4 {
 		(byte_or_double_word[0] as usize) << 24 | (byte_or_double_word[1] as usize) << 16 | (byte_or_double_word[2] as usize) << 8 | byte_or_double_word[3] as usize
 	} else {
-		return Err(Box::from("buffer size must be 1 or 4"));
+		return Err(Box::from("buffer size must be 1, 2 or 4"));
 	}];
 
 	if buffer.len() == 0 {
@@ -79,10 +465,10 @@ pub fn read_string<const N: usize>(stream: &mut TcpStream, byte_or_double_word:
 	Ok(String::from_utf8(buffer)?)
 }
 
-pub fn send_error(stream: &mut TcpStream, double_word: &mut [u8; 4], message: String) -> Result<()> {
+pub fn send_error<S: Stream + ?Sized>(stream: &mut S, double_word: &mut [u8; 4], message: String) -> Result<()> {
 	let message_length: usize = message.len();
 
-	error!("{} to {}\n", message, stream.peer_addr()?);
+	error!("{} to {}\n", message, stream.get_address());
 
 	double_word[0] = (message_length >> 24) as u8;
 	double_word[1] = (message_length >> 16) as u8;
@@ -98,6 +484,64 @@ pub fn send_error(stream: &mut TcpStream, double_word: &mut [u8; 4], message: St
 	Ok(())
 }
 
+pub fn send_value(stream: &mut (impl Write + ?Sized), double_word: &mut [u8; 4], value: &[u8]) -> Result<()> {
+	let value_length: usize = value.len();
+
+	double_word[0] = (value_length >> 24) as u8;
+	double_word[1] = (value_length >> 16) as u8;
+	double_word[2] = (value_length >> 8) as u8;
+	double_word[3] = value_length as u8;
+
+	write_all_vectored(stream, &mut [
+		IoSlice::new(OPERATION_VALUE),
+		IoSlice::new(double_word),
+		IoSlice::new(value)
+	])
+}
+
+pub fn write_all_vectored(stream: &mut (impl Write + ?Sized), mut buffers: &mut [IoSlice<'_>]) -> Result<()> {
+	// write_vectored may write partially, which is likely once there are many buffers
+	while buffers.len() != 0 {
+		let length: usize = stream.write_vectored(buffers)?;
+
+		if length == 0 {
+			return Err(Box::from(_Error::from(ErrorKind::WriteZero)));
+		}
+
+		IoSlice::advance_slices(&mut buffers, length);
+	}
+
+	Ok(())
+}
+
+pub fn send_values(stream: &mut (impl Write + ?Sized), double_word: &mut [u8; 4], values: &[String]) -> Result<()> {
+	let values_length: usize = values.len();
+	let mut lengths: Vec<[u8; 4]> = Vec::with_capacity(values_length);
+
+	for value in values {
+		let value_length: usize = value.len();
+
+		lengths.push([(value_length >> 24) as u8, (value_length >> 16) as u8, (value_length >> 8) as u8, value_length as u8]);
+	}
+
+	double_word[0] = (values_length >> 24) as u8;
+	double_word[1] = (values_length >> 16) as u8;
+	double_word[2] = (values_length >> 8) as u8;
+	double_word[3] = values_length as u8;
+
+	let mut buffers: Vec<IoSlice> = Vec::with_capacity(values_length * 2 + 2);
+
+	buffers.push(IoSlice::new(OPERATION_VALUES));
+	buffers.push(IoSlice::new(double_word));
+
+	for (length, value) in zip(&lengths, values) {
+		buffers.push(IoSlice::new(length));
+		buffers.push(IoSlice::new(value.as_bytes()));
+	}
+
+	write_all_vectored(stream, &mut buffers)
+}
+
 pub struct Version {
 	major: u8,
 	minor: u8,
@@ -105,7 +549,7 @@ pub struct Version {
 }
 
 impl Version {
-	pub fn new(major: u8, minor: u8, patch: u8) -> Self {
+	pub const fn new(major: u8, minor: u8, patch: u8) -> Self {
 		Version {
 			major: major,
 			minor: minor,
@@ -183,180 +627,2687 @@ impl PartialOrd for Version {
 	}
 }
 
+// clients from this version on send keys and read KEY_LIST keys with u16 lengths, older ones keep u8
+const KEY_SIZE_16: Version = Version::new(0, 1, 0);
+
 impl Display for Version {
 	fn fmt(self: &Self, formatter: &mut Formatter<'_>) -> _Result {
 		write!(formatter, "{}.{}.{}", self.major, self.minor, self.patch)
 	}
 }
 
-pub fn serve() -> Result<()> {
-	info!("starting dQache {} on {}\n", ARGUMENT.version, ARGUMENT.platform);
+// storage keeps the absolute expiry, so a promoted entry only gets whatever lifetime it has left
+fn load(storage: &Storage, key: &str) -> Result<Option<(Value, Option<u64>)>> {
+	let bytes: Vec<u8> = if let Some(bytes) = storage.read(key)? {
+		bytes
+	} else {
+		return Ok(None);
+	};
+	let ttl: Option<u64> = if let Some(expires_at) = storage.read_ttl(key)? {
+		let now: u64 = unix_epoch_millis()?;
 
-	let cache: Arc<Mutex<Cache>> = Arc::new(Mutex::new(Cache::new(ARGUMENT.model, ARGUMENT.capacity)?));
-	let storage: Arc<RwLock<Storage>> = Arc::new(RwLock::new(Storage::new(&ARGUMENT.directory)?));
-	let thread_pool: ThreadPool = ThreadPool::new(ARGUMENT.thread_count)?;
-	let listener: TcpListener = TcpListener::bind((ARGUMENT.host, ARGUMENT.port))?;
+		if expires_at <= now {
+			return Ok(None);
+		}
+
+		Some(expires_at - now)
+	} else {
+		None
+	};
 
-	info!("lisening on {}:{} with {} threads\n", ARGUMENT.host, ARGUMENT.port, ARGUMENT.thread_count);
+	Ok(Some((Value::from_bytes(bytes)?, ttl)))
+}
 
-	for stream in listener.incoming() {
-		let mut stream: TcpStream = stream?;
-		let cache: Arc<Mutex<Cache>> = cache.clone();
-		let storage: Arc<RwLock<Storage>> = storage.clone();
+// read through the cache, promoting storage hits the same way GET always has
+fn fetch(cache: &RwLock<Cache>, storage: &RwLock<Storage>, key: &str) -> Result<Option<Value>> {
+	if let Some(entry) = cache.write()
+		.map_err(|error: PoisonError<RwLockWriteGuard<'_, Cache>>| error.to_string())?
+		.get(key)? {
+		return Ok(Some(entry.value.clone()));
+	}
 
-		stream.set_read_timeout(Some(Duration::from_secs(60)))?;
-		stream.set_nodelay(true)?;
+	let loaded: Option<(Value, Option<u64>)> = load(&*storage.read()
+		.map_err(|error: PoisonError<RwLockReadGuard<'_, Storage>>| error.to_string())?, key)?;
+	let (value, ttl): (Value, Option<u64>) = if let Some(loaded) = loaded {
+		loaded
+	} else {
+		return Ok(None);
+	};
 
-		thread_pool.execute(move || {
-			let mut double_word: [u8; 4] = [0; 4];
+	cache.write()
+		.map_err(|error: PoisonError<RwLockWriteGuard<'_, Cache>>| error.to_string())?
+		.set(key, Entry::new(value.clone())?, ttl)?;
 
-			if let Err(error) = (|| -> Result<()> {
-				stream.write_vectored(&[
-					IoSlice::new(OPERATION_READY),
-					IoSlice::new(&ARGUMENT.version.as_bytes())
-				])?;
+	Ok(Some(value))
+}
 
-				// 중략
-				stream.read_exact(&mut double_word)?;
+fn fetch_string(cache: &RwLock<Cache>, storage: &RwLock<Storage>, key: &str) -> Result<Option<String>> {
+	match fetch(cache, storage, key)? {
+		Some(Value::String(value)) => Ok(Some(value)),
+		Some(_) => Err(Box::from("value must be a string")),
+		None => Ok(None)
+	}
+}
 
-				if double_word[0] != OPERATION_HELLO[0] {
-					return Err(Box::from("handshake must start with HELLO operation"));
-				}
+fn store(cache: &RwLock<Cache>, storage: &RwLock<Storage>, key: &str, value: Value) -> Result<()> {
+	let bytes: Vec<u8> = value.to_bytes();
+	let mut guard: RwLockWriteGuard<'_, Cache> = cache.write()
+		.map_err(|error: PoisonError<RwLockWriteGuard<'_, Cache>>| error.to_string())?;
 
-				if let Ok(version) = Version::try_from(&double_word[1..4]) {
-					if version > ARGUMENT.version {
-						return Err(Box::from(format!("client version must be less than or equal to {}", ARGUMENT.version)));
-					}
+	guard.set(key, Entry::new(value)?, None)?;
+	guard.mark_written();
+	guard.notify(key, KeyspaceEvent::Set)?;
+	drop(guard);
+
+	let storage_guard: RwLockWriteGuard<'_, Storage> = storage.write()
+		.map_err(|error: PoisonError<RwLockWriteGuard<'_, Storage>>| error.to_string())?;
+
+	// a new value starts without a lifetime
+	storage_guard.delete_ttl(key)?;
+	storage_guard.write(key, &bytes)?;
+
+	Ok(())
+}
+
+// the cache lock is held through the storage write, so concurrent modifications land in order
+fn modify_value<T>(cache: &RwLock<Cache>, storage: &RwLock<Storage>, key: &str, created: Option<Value>, modify: impl FnOnce(&mut Value) -> Result<T>) -> Result<Option<T>> {
+	let mut guard: RwLockWriteGuard<'_, Cache> = cache.write()
+		.map_err(|error: PoisonError<RwLockWriteGuard<'_, Cache>>| error.to_string())?;
+
+	let mut is_created: bool = false;
+
+	if !guard.contains(key)? {
+		let loaded: Option<(Value, Option<u64>)> = load(&*storage.read()
+			.map_err(|error: PoisonError<RwLockReadGuard<'_, Storage>>| error.to_string())?, key)?;
+		let (value, ttl): (Value, Option<u64>) = if let Some(loaded) = loaded {
+			loaded
+		} else if let Some(value) = created {
+			is_created = true;
+
+			(value, None)
+		} else {
+			return Ok(None);
+		};
+
+		guard.set(key, Entry::new(value)?, ttl)?;
+	}
+
+	let entry: &mut Entry = if let Some(entry) = guard.get_mut(key)? {
+		entry
+	} else {
+		return Ok(None);
+	};
+	let result: T = modify(&mut entry.value)?;
+	let is_empty: bool = entry.value.is_empty();
+	let bytes: Vec<u8> = entry.value.to_bytes();
+
+	guard.mark_written();
+
+	let storage_guard: RwLockWriteGuard<'_, Storage> = storage.write()
+		.map_err(|error: PoisonError<RwLockWriteGuard<'_, Storage>>| error.to_string())?;
+
+	// an emptied collection goes away instead of lingering as an empty value
+	if is_empty {
+		guard.remove(key);
+		storage_guard.delete(key)?;
+		guard.notify(key, KeyspaceEvent::Delete)?;
+	} else {
+		// whatever expiry an expired predecessor left behind must not apply to a fresh value
+		if is_created {
+			storage_guard.delete_ttl(key)?;
+		}
+
+		storage_guard.write(key, &bytes)?;
+		guard.notify(key, KeyspaceEvent::Set)?;
+	}
+
+	Ok(Some(result))
+}
+
+fn modify_list<T>(cache: &RwLock<Cache>, storage: &RwLock<Storage>, key: &str, is_created: bool, modify: impl FnOnce(&mut List) -> T) -> Result<Option<T>> {
+	modify_value(cache, storage, key, if is_created {
+		Some(Value::List(List::new()))
+	} else {
+		None
+	}, |value: &mut Value| if let Value::List(list) = value {
+		Ok(modify(list))
+	} else {
+		Err(Box::from("value must be a list"))
+	})
+}
+
+fn modify_hash<T>(cache: &RwLock<Cache>, storage: &RwLock<Storage>, key: &str, is_created: bool, modify: impl FnOnce(&mut Hash) -> Result<T>) -> Result<Option<T>> {
+	modify_value(cache, storage, key, if is_created {
+		Some(Value::Hash(Hash::new()))
+	} else {
+		None
+	}, |value: &mut Value| if let Value::Hash(hash) = value {
+		modify(hash)
+	} else {
+		Err(Box::from("value must be a hash"))
+	})
+}
+
+fn modify_sorted_set<T>(cache: &RwLock<Cache>, storage: &RwLock<Storage>, key: &str, is_created: bool, modify: impl FnOnce(&mut SortedSet) -> T) -> Result<Option<T>> {
+	modify_value(cache, storage, key, if is_created {
+		Some(Value::SortedSet(SortedSet::new()))
+	} else {
+		None
+	}, |value: &mut Value| if let Value::SortedSet(sorted_set) = value {
+		Ok(modify(sorted_set))
+	} else {
+		Err(Box::from("value must be a sorted set"))
+	})
+}
+
+fn fetch_sorted_set(cache: &RwLock<Cache>, storage: &RwLock<Storage>, key: &str) -> Result<Option<SortedSet>> {
+	match fetch(cache, storage, key)? {
+		Some(Value::SortedSet(sorted_set)) => Ok(Some(sorted_set)),
+		Some(_) => Err(Box::from("value must be a sorted set")),
+		None => Ok(None)
+	}
+}
+
+// the directory is scanned before either lock is taken, and every candidate is checked again under both
+fn expire(cache: &RwLock<Cache>, storage: &RwLock<Storage>) -> Result<()> {
+	let now: u64 = unix_epoch_millis()?;
+	let mut keys: HashSet<String> = storage.read()
+		.map_err(|error: PoisonError<RwLockReadGuard<'_, Storage>>| error.to_string())?
+		.expired_keys(now)?
+		.into_iter()
+		.collect();
+	let mut guard: RwLockWriteGuard<'_, Cache> = cache.write()
+		.map_err(|error: PoisonError<RwLockWriteGuard<'_, Cache>>| error.to_string())?;
+	let storage_guard: RwLockWriteGuard<'_, Storage> = storage.write()
+		.map_err(|error: PoisonError<RwLockWriteGuard<'_, Storage>>| error.to_string())?;
+
+	keys.extend(guard.remove_expired()?);
+
+	for key in &keys {
+		if storage_guard.read_ttl(key)?
+			.is_some_and(|expires_at: u64| expires_at <= now) {
+			guard.remove(key);
+			storage_guard.delete(key)?;
+			guard.notify(key, KeyspaceEvent::Expired)?;
+		}
+	}
+
+	Ok(())
+}
+
+fn remove(cache: &RwLock<Cache>, storage: &RwLock<Storage>, key: &str) -> Result<bool> {
+	let mut guard: RwLockWriteGuard<'_, Cache> = cache.write()
+		.map_err(|error: PoisonError<RwLockWriteGuard<'_, Cache>>| error.to_string())?;
+
+	guard.remove(key);
+	guard.mark_written();
+
+	let is_removed: bool = storage.write()
+		.map_err(|error: PoisonError<RwLockWriteGuard<'_, Storage>>| error.to_string())?
+		.delete(key)?;
+
+	if is_removed {
+		guard.notify(key, KeyspaceEvent::Delete)?;
+	}
+
+	Ok(is_removed)
+}
+
+#[derive(Clone)]
+struct Context {
+	cache: Arc<RwLock<Cache>>,
+	storage: Arc<RwLock<Storage>>,
+	slow_log: Arc<Mutex<SlowLog>>,
+	telemetry: Arc<Telemetry>,
+	node_id: Arc<String>,
+	gate: Arc<RwLock<()>>,
+	keyspace: Arc<Keyspace>,
+	stats: Arc<Mutex<Stats>>
+}
+
+// state that belongs to one connection and dies with it
+struct Session {
+	watches: HashMap<String, u64>,
+	scan_keys: Vec<String>,
+	name: Option<String>,
+	is_key_size_16: bool
+}
+
+impl Session {
+	fn describe(self: &Self, address: String) -> String {
+		if let Some(name) = &self.name {
+			format!("{} ({})", address, name)
+		} else {
+			address
+		}
+	}
+}
+
+// lets a buffered operation run through execute while its responses still reach the client
+struct Frame<'a> {
+	buffer: &'a [u8],
+	stream: &'a mut dyn Stream
+}
+
+impl<'a> Read for Frame<'a> {
+	fn read(self: &mut Self, buffer: &mut [u8]) -> IoResult<usize> {
+		self.buffer.read(buffer)
+	}
+}
+
+impl<'a> Write for Frame<'a> {
+	fn write(self: &mut Self, buffer: &[u8]) -> IoResult<usize> {
+		self.stream.write(buffer)
+	}
 
-					info!("client connected with {} from {}\n", version, stream.peer_addr()?);
+	fn write_vectored(self: &mut Self, buffers: &[IoSlice<'_>]) -> IoResult<usize> {
+		self.stream.write_vectored(buffers)
+	}
+
+	fn flush(self: &mut Self) -> IoResult<()> {
+		self.stream.flush()
+	}
+}
+
+impl<'a> Stream for Frame<'a> {
+	fn get_address(self: &Self) -> String {
+		self.stream.get_address()
+	}
+
+	fn set_timeout(self: &Self, _timeout: Duration) -> Result<()> {
+		Ok(())
+	}
+
+	fn set_no_delay(self: &Self) -> Result<()> {
+		Ok(())
+	}
+
+	fn is_connected(self: &Self) -> bool {
+		self.stream.is_connected()
+	}
+}
+
+// keys are read in the order the operation lays them out and anything after the last one is left unread
+fn get_keys(arguments: &mut (impl Read + ?Sized), operation: &[u8; 1], byte: &mut [u8; 1], double_word: &mut [u8; 4], is_key_size_16: bool) -> Result<Vec<String>> {
+	let mut keys: Vec<String> = Vec::new();
+
+	match operation {
+		OPERATION_SET | OPERATION_GET | OPERATION_DELETE => {
+			keys.push(read_key(arguments, byte, is_key_size_16)?);
+		},
+		OPERATION_APPEND | OPERATION_STRLEN | OPERATION_INCR | OPERATION_DECR | OPERATION_INCRBY | OPERATION_DECRBY | OPERATION_SETNX | OPERATION_GETSET | OPERATION_GETSET_EX | OPERATION_GETDEL | OPERATION_SCARD | OPERATION_WATCH | OPERATION_OBJECT_ENCODING | OPERATION_LPUSH | OPERATION_RPUSH | OPERATION_LPOP | OPERATION_RPOP | OPERATION_LLEN | OPERATION_LRANGE | OPERATION_LINSERT | OPERATION_LPOS | OPERATION_HSET | OPERATION_HGET | OPERATION_HDEL | OPERATION_HGETALL | OPERATION_HKEYS | OPERATION_HVALS | OPERATION_HLEN | OPERATION_HEXISTS | OPERATION_HRANDFIELD | OPERATION_HMSET | OPERATION_HMGET | OPERATION_EXISTS | OPERATION_HINCRBY | OPERATION_HINCRBYFLOAT | OPERATION_ZADD | OPERATION_ZREM | OPERATION_ZPOPMIN | OPERATION_ZPOPMAX | OPERATION_ZRANGE | OPERATION_ZRANK | OPERATION_ZSCORE | OPERATION_ZRANDMEMBER | OPERATION_ZRANGEBYSCORE | OPERATION_ZRANGEBYLEX | OPERATION_EXPIRE | OPERATION_TTL | OPERATION_PERSIST | OPERATION_PEXPIRE | OPERATION_PTTL | OPERATION_SUBSCRIBE_KEYSPACE | OPERATION_GETEX_PERSIST | OPERATION_WAIT_FOR_KEY => {
+			keys.push(read_key(arguments, byte, is_key_size_16)?);
+		},
+		OPERATION_RENAME | OPERATION_COPY => {
+			keys.push(read_key(arguments, byte, is_key_size_16)?);
+			keys.push(read_key(arguments, byte, is_key_size_16)?);
+		},
+		OPERATION_SINTERSTORE | OPERATION_SUNIONSTORE | OPERATION_SDIFFSTORE => {
+			for _ in 0..3 {
+				keys.push(read_key(arguments, byte, is_key_size_16)?);
+			}
+		},
+		OPERATION_GETDEL_MANY | OPERATION_MGET | OPERATION_MDEL | OPERATION_ZDIFF | OPERATION_BLPOP | OPERATION_BRPOP => {
+			arguments.read_exact(byte)?;
+
+			for _ in 0..byte[0] {
+				keys.push(read_key(arguments, byte, is_key_size_16)?);
+			}
+		},
+		OPERATION_MSET => {
+			arguments.read_exact(byte)?;
+
+			for _ in 0..byte[0] {
+				keys.push(read_key(arguments, byte, is_key_size_16)?);
+				read_string::<4>(arguments, double_word)?;
+			}
+		},
+		OPERATION_ZUNIONSTORE | OPERATION_ZINTERSTORE | OPERATION_ZDIFFSTORE => {
+			keys.push(read_key(arguments, byte, is_key_size_16)?);
+			arguments.read_exact(byte)?;
+
+			for _ in 0..byte[0] {
+				keys.push(read_key(arguments, byte, is_key_size_16)?);
+			}
+		},
+		OPERATION_EXEC_IF_UNCHANGED => {
+			keys.push(read_key(arguments, byte, is_key_size_16)?);
+			read_double_word(arguments, double_word)?;
+			arguments.read_exact(byte)?;
+
+			let frame_operation: [u8; 1] = *byte;
+
+			keys.extend(get_keys(arguments, &frame_operation, byte, double_word, is_key_size_16)?);
+		},
+		OPERATION_NOOP | OPERATION_SRANDMEMBER | OPERATION_SLOWLOG_GET | OPERATION_SLOWLOG_LEN | OPERATION_SLOWLOG_RESET | OPERATION_STATS | OPERATION_CONFIG_VALIDATE | OPERATION_CLUSTER_MYID | OPERATION_COMMAND_COUNT | OPERATION_SUBSCRIBE_STATS | OPERATION_UNSUBSCRIBE_STATS | OPERATION_WAIT_QUIET | OPERATION_RANDOMKEY | OPERATION_KEYS | OPERATION_SCAN | OPERATION_CONFIG_SET | OPERATION_COMMAND_GETKEYS | OPERATION_UNSUBSCRIBE_KEYSPACE | OPERATION_SUBSCRIBE_KEYSPACE_PATTERN | OPERATION_FLUSH | OPERATION_CLIENT_SETNAME | OPERATION_CLIENT_GETNAME | OPERATION_PING | OPERATION_INFO | OPERATION_QUIT => {},
+		_ => {
+			return Err(Box::from("operation must be valid"));
+		}
+	}
+
+	Ok(keys)
+}
+
+// appends a length prefix and what it counts, without checking that the content is valid
+fn copy_string<const N: usize>(stream: &mut (impl Read + ?Sized), byte_or_double_word: &mut [u8; N], frame: &mut Vec<u8>) -> Result<()> {
+	stream.read_exact(byte_or_double_word)?;
+	frame.extend_from_slice(byte_or_double_word);
+
+	let length: usize = byte_or_double_word.iter()
+		.fold(0, |length: usize, byte: &u8| length << 8 | *byte as usize);
+
+	copy_exact(stream, length, frame)
+}
+
+fn copy_exact(stream: &mut (impl Read + ?Sized), length: usize, frame: &mut Vec<u8>) -> Result<()> {
+	let start: usize = frame.len();
+
+	frame.resize(start + length, 0);
+	stream.read_exact(&mut frame[start..])?;
+
+	Ok(())
+}
+
+fn copy_key(stream: &mut (impl Read + ?Sized), byte: &mut [u8; 1], is_key_size_16: bool, frame: &mut Vec<u8>) -> Result<()> {
+	if is_key_size_16 {
+		copy_string::<2>(stream, &mut [0; 2], frame)
+	} else {
+		copy_string::<1>(stream, byte, frame)
+	}
+}
+
+// the arguments of a gated operation, read whole before the gate is taken so a stalled client cannot hold it
+fn read_frame(stream: &mut (impl Read + ?Sized), operation: &[u8; 1], byte: &mut [u8; 1], double_word: &mut [u8; 4], is_key_size_16: bool) -> Result<Vec<u8>> {
+	let mut frame: Vec<u8> = Vec::new();
+
+	match operation {
+		OPERATION_GET | OPERATION_DELETE | OPERATION_SCARD | OPERATION_WATCH | OPERATION_OBJECT_ENCODING | OPERATION_LPOP | OPERATION_RPOP | OPERATION_LLEN | OPERATION_HGETALL | OPERATION_HKEYS | OPERATION_HVALS | OPERATION_HLEN | OPERATION_EXISTS | OPERATION_TTL | OPERATION_PERSIST | OPERATION_PTTL | OPERATION_GETEX_PERSIST | OPERATION_GETDEL | OPERATION_INCR | OPERATION_DECR | OPERATION_STRLEN => {
+			copy_key(stream, byte, is_key_size_16, &mut frame)?;
+		},
+		OPERATION_KEYS | OPERATION_CLIENT_SETNAME => {
+			copy_string::<1>(stream, byte, &mut frame)?;
+		},
+		OPERATION_SET | OPERATION_LPUSH | OPERATION_RPUSH | OPERATION_ZRANK | OPERATION_ZSCORE | OPERATION_SETNX | OPERATION_GETSET | OPERATION_APPEND => {
+			copy_key(stream, byte, is_key_size_16, &mut frame)?;
+			copy_string::<4>(stream, double_word, &mut frame)?;
+		},
+		OPERATION_CONFIG_VALIDATE | OPERATION_CONFIG_SET => {
+			copy_string::<1>(stream, byte, &mut frame)?;
+			copy_string::<4>(stream, double_word, &mut frame)?;
+		},
+		OPERATION_GETSET_EX => {
+			copy_key(stream, byte, is_key_size_16, &mut frame)?;
+			copy_string::<4>(stream, double_word, &mut frame)?;
+			copy_exact(stream, 4, &mut frame)?;
+		},
+		OPERATION_LPOS => {
+			copy_key(stream, byte, is_key_size_16, &mut frame)?;
+			copy_string::<4>(stream, double_word, &mut frame)?;
+			copy_exact(stream, 1, &mut frame)?;
+
+			let flags: u8 = frame[frame.len() - 1];
+
+			if flags & 0b01 != 0 {
+				copy_exact(stream, 1, &mut frame)?;
+			}
+
+			if flags & 0b10 != 0 {
+				copy_exact(stream, 4, &mut frame)?;
+			}
+		},
+		OPERATION_LINSERT => {
+			copy_key(stream, byte, is_key_size_16, &mut frame)?;
+			copy_exact(stream, 1, &mut frame)?;
+			copy_string::<4>(stream, double_word, &mut frame)?;
+			copy_string::<4>(stream, double_word, &mut frame)?;
+		},
+		OPERATION_EXPIRE => {
+			copy_key(stream, byte, is_key_size_16, &mut frame)?;
+			copy_exact(stream, 4, &mut frame)?;
+		},
+		OPERATION_LRANGE | OPERATION_ZRANGE | OPERATION_PEXPIRE | OPERATION_INCRBY | OPERATION_DECRBY => {
+			copy_key(stream, byte, is_key_size_16, &mut frame)?;
+			copy_exact(stream, 8, &mut frame)?;
+		},
+		OPERATION_ZPOPMIN | OPERATION_ZPOPMAX => {
+			copy_key(stream, byte, is_key_size_16, &mut frame)?;
+			copy_exact(stream, 1, &mut frame)?;
+		},
+		OPERATION_HRANDFIELD | OPERATION_ZRANDMEMBER => {
+			copy_key(stream, byte, is_key_size_16, &mut frame)?;
+			copy_exact(stream, 2, &mut frame)?;
+		},
+		OPERATION_HGET | OPERATION_HDEL | OPERATION_HEXISTS => {
+			copy_key(stream, byte, is_key_size_16, &mut frame)?;
+			copy_string::<1>(stream, byte, &mut frame)?;
+		},
+		OPERATION_RENAME => {
+			copy_key(stream, byte, is_key_size_16, &mut frame)?;
+			copy_key(stream, byte, is_key_size_16, &mut frame)?;
+		},
+		OPERATION_COPY => {
+			copy_key(stream, byte, is_key_size_16, &mut frame)?;
+			copy_key(stream, byte, is_key_size_16, &mut frame)?;
+			copy_exact(stream, 1, &mut frame)?;
+		},
+		OPERATION_HSET => {
+			copy_key(stream, byte, is_key_size_16, &mut frame)?;
+			copy_string::<1>(stream, byte, &mut frame)?;
+			copy_string::<4>(stream, double_word, &mut frame)?;
+		},
+		OPERATION_HINCRBY | OPERATION_HINCRBYFLOAT => {
+			copy_key(stream, byte, is_key_size_16, &mut frame)?;
+			copy_string::<1>(stream, byte, &mut frame)?;
+			copy_exact(stream, 8, &mut frame)?;
+		},
+		OPERATION_SINTERSTORE | OPERATION_SUNIONSTORE | OPERATION_SDIFFSTORE => {
+			for _ in 0..3 {
+				copy_key(stream, byte, is_key_size_16, &mut frame)?;
+			}
+		},
+		OPERATION_ZRANGEBYLEX => {
+			copy_key(stream, byte, is_key_size_16, &mut frame)?;
+			copy_string::<1>(stream, byte, &mut frame)?;
+			copy_string::<1>(stream, byte, &mut frame)?;
+		},
+		OPERATION_ZADD => {
+			copy_key(stream, byte, is_key_size_16, &mut frame)?;
+			copy_exact(stream, 8, &mut frame)?;
+			copy_string::<4>(stream, double_word, &mut frame)?;
+		},
+		OPERATION_ZRANGEBYSCORE => {
+			copy_key(stream, byte, is_key_size_16, &mut frame)?;
+			copy_exact(stream, 20, &mut frame)?;
+		},
+		OPERATION_GETDEL_MANY | OPERATION_MGET | OPERATION_MDEL | OPERATION_ZDIFF => {
+			copy_exact(stream, 1, &mut frame)?;
+
+			for _ in 0..frame[0] {
+				copy_key(stream, byte, is_key_size_16, &mut frame)?;
+			}
+		},
+		OPERATION_MSET => {
+			copy_exact(stream, 1, &mut frame)?;
+
+			for _ in 0..frame[0] {
+				copy_key(stream, byte, is_key_size_16, &mut frame)?;
+				copy_string::<4>(stream, double_word, &mut frame)?;
+			}
+		},
+		OPERATION_HMGET | OPERATION_ZDIFFSTORE | OPERATION_ZUNIONSTORE | OPERATION_ZINTERSTORE => {
+			copy_key(stream, byte, is_key_size_16, &mut frame)?;
+			copy_exact(stream, 1, &mut frame)?;
+
+			// the fields of HMGET keep their u8 length, the keys of the others follow the session
+			for _ in 0..frame[frame.len() - 1] {
+				if operation == OPERATION_HMGET {
+					copy_string::<1>(stream, byte, &mut frame)?;
 				} else {
-					return Err(Box::from("client version must be invalid\n"));
+					copy_key(stream, byte, is_key_size_16, &mut frame)?;
+				}
+			}
+
+			// the weights follow the keys with a count of their own
+			if operation == OPERATION_ZUNIONSTORE || operation == OPERATION_ZINTERSTORE {
+				copy_exact(stream, 1, &mut frame)?;
+				copy_exact(stream, frame[frame.len() - 1] as usize * 8, &mut frame)?;
+			}
+		},
+		OPERATION_HMSET => {
+			copy_key(stream, byte, is_key_size_16, &mut frame)?;
+			copy_exact(stream, 1, &mut frame)?;
+
+			for _ in 0..frame[frame.len() - 1] {
+				copy_string::<1>(stream, byte, &mut frame)?;
+				copy_string::<4>(stream, double_word, &mut frame)?;
+			}
+		},
+		OPERATION_ZREM => {
+			copy_key(stream, byte, is_key_size_16, &mut frame)?;
+			copy_exact(stream, 1, &mut frame)?;
+
+			for _ in 0..frame[frame.len() - 1] {
+				copy_string::<4>(stream, double_word, &mut frame)?;
+			}
+		},
+		OPERATION_SRANDMEMBER | OPERATION_SLOWLOG_GET => {
+			copy_exact(stream, 4, &mut frame)?;
+		},
+		OPERATION_SCAN => {
+			copy_exact(stream, 5, &mut frame)?;
+		},
+		OPERATION_COMMAND_GETKEYS => {
+			copy_exact(stream, 1, &mut frame)?;
+			copy_string::<4>(stream, double_word, &mut frame)?;
+		},
+		OPERATION_PING => {
+			copy_string::<4>(stream, double_word, &mut frame)?;
+		},
+		OPERATION_NOOP | OPERATION_SLOWLOG_LEN | OPERATION_SLOWLOG_RESET | OPERATION_STATS | OPERATION_CLUSTER_MYID | OPERATION_COMMAND_COUNT | OPERATION_UNSUBSCRIBE_STATS | OPERATION_RANDOMKEY | OPERATION_UNSUBSCRIBE_KEYSPACE | OPERATION_FLUSH | OPERATION_CLIENT_GETNAME | OPERATION_INFO | OPERATION_QUIT => {},
+		_ => {
+			return Err(Box::from("operation must be valid"));
+		}
+	}
+
+	Ok(frame)
+}
+
+fn execute(stream: &mut dyn Stream, operation: &[u8; 1], byte: &mut [u8; 1], double_word: &mut [u8; 4], context: &Context, session: &mut Session) -> Result<()> {
+	let Context { cache, storage, slow_log, telemetry, node_id, gate, keyspace, stats }: &Context = context;
+
+	match operation {
+		OPERATION_SET => {
+			let key: String = read_key(stream, byte, session.is_key_size_16)?;
+			let value: String = read_string::<4>(stream, double_word)?;
+
+			store(&cache, &storage, &key, Value::String(value))?;
+
+			stream.write(OPERATION_OK)?;
+		},
+		OPERATION_APPEND => {
+			let key: String = read_key(stream, byte, session.is_key_size_16)?;
+			let suffix: String = read_string::<4>(stream, double_word)?;
+
+			// the cached string grows in place, and a missing key starts out empty
+			let length: usize = modify_value(&cache, &storage, &key, Some(Value::String(String::new())), |value: &mut Value| if let Value::String(value) = value {
+				if value.len() + suffix.len() > u32::MAX as usize {
+					return Err(Box::from("value must not exceed 4294967295 bytes"));
 				}
 
+				value.push_str(&suffix);
+
+				Ok(value.len())
+			} else {
+				Err(Box::from("value must be a string"))
+			})?.unwrap_or_default();
+
+			send_value(stream, double_word, length.to_string().as_bytes())?;
+		},
+		OPERATION_INCR | OPERATION_DECR | OPERATION_INCRBY | OPERATION_DECRBY => {
+			let key: String = read_key(stream, byte, session.is_key_size_16)?;
+			let mut quad_word: [u8; 8] = [0; 8];
+			let delta: i64 = if operation == OPERATION_INCRBY || operation == OPERATION_DECRBY {
+				read_quad_word(stream, &mut quad_word)? as i64
+			} else {
+				1
+			};
+			let is_decrement: bool = operation == OPERATION_DECR || operation == OPERATION_DECRBY;
+
+			// a missing key counts as zero and an existing lifetime is kept
+			let value: String = modify_value(&cache, &storage, &key, Some(Value::String(0.to_string())), |value: &mut Value| {
+				let current: i64 = value.as_integer()?;
+				let integer: i64 = if is_decrement {
+					current.checked_sub(delta)
+				} else {
+					current.checked_add(delta)
+				}.ok_or("value must not overflow")?;
+
+				*value = Value::String(integer.to_string());
+
+				Ok(integer.to_string())
+			})?.unwrap_or_default();
+
+			send_value(stream, double_word, value.as_bytes())?;
+		},
+		OPERATION_GETSET | OPERATION_GETSET_EX => {
+			let key: String = read_key(stream, byte, session.is_key_size_16)?;
+			let value: String = read_string::<4>(stream, double_word)?;
+			let ttl: Option<u64> = if operation == OPERATION_GETSET_EX {
+				Some(read_double_word(stream, double_word)? as u64 * 1000)
+					.filter(|ttl: &u64| *ttl != 0)
+			} else {
+				None
+			};
+			let mut guard: RwLockWriteGuard<'_, Cache> = cache.write()
+				.map_err(|error: PoisonError<RwLockWriteGuard<'_, Cache>>| error.to_string())?;
+			let old_value: Option<Value> = if let Some(entry) = guard.get(&key)? {
+				Some(entry.value.clone())
+			} else {
+				load(&*storage.read()
+					.map_err(|error: PoisonError<RwLockReadGuard<'_, Storage>>| error.to_string())?, &key)?
+					.map(|(value, _): (Value, Option<u64>)| value)
+			};
+			let old_value: Option<String> = match old_value {
+				Some(Value::String(old_value)) => Some(old_value),
+				Some(_) => return Err(Box::from("value must be a string")),
+				None => None
+			};
+
+			guard.set(&key, Entry::new(Value::String(value.clone()))?, ttl)?;
+			guard.mark_written();
+
+			let expires_at: Option<u64> = guard.expiry(&key)?
+				.flatten();
+			// the cache lock is kept until storage holds the value too, so another writer cannot slip in between
+			let storage_guard: RwLockWriteGuard<'_, Storage> = storage.write()
+				.map_err(|error: PoisonError<RwLockWriteGuard<'_, Storage>>| error.to_string())?;
+
+			// the replaced value took its lifetime with it, only GETSET_EX may bring a new one
+			storage_guard.delete_ttl(&key)?;
+			storage_guard.write(&key, value.as_bytes())?;
+
+			if let Some(expires_at) = expires_at {
+				storage_guard.write_ttl(&key, expires_at)?;
+			}
+
+			drop(storage_guard);
+
+			guard.notify(&key, KeyspaceEvent::Set)?;
+
+			drop(guard);
+
+			if let Some(old_value) = old_value {
+				send_value(stream, double_word, old_value.as_bytes())?;
+			} else {
 				stream.write(OPERATION_OK)?;
+			}
+		},
+		OPERATION_SETNX => {
+			let key: String = read_key(stream, byte, session.is_key_size_16)?;
+			let value: String = read_string::<4>(stream, double_word)?;
+
+			// the check and the write share both locks, so two clients racing for the key cannot both win
+			let mut guard: RwLockWriteGuard<'_, Cache> = cache.write()
+				.map_err(|error: PoisonError<RwLockWriteGuard<'_, Cache>>| error.to_string())?;
+			let storage_guard: RwLockWriteGuard<'_, Storage> = storage.write()
+				.map_err(|error: PoisonError<RwLockWriteGuard<'_, Storage>>| error.to_string())?;
+			let now: u64 = unix_epoch_millis()?;
+
+			if guard.contains(&key)? || (storage_guard.exists(&key)? && !storage_guard.read_ttl(&key)?
+				.is_some_and(|expires_at: u64| expires_at <= now)) {
+				drop(storage_guard);
+				drop(guard);
 
-				Ok(())
-			})() {
-				let _ = send_error(&mut stream, &mut double_word, error.to_string());
+				stream.write(OPERATION_OK)?;
 
-				return;
+				return Ok(());
 			}
 
-			let mut byte: [u8; 1] = [0];
+			storage_guard.delete_ttl(&key)?;
+			storage_guard.write(&key, value.as_bytes())?;
+			guard.set(&key, Entry::new(Value::String(value))?, None)?;
+			guard.mark_written();
+			guard.notify(&key, KeyspaceEvent::Set)?;
+			drop(storage_guard);
+			drop(guard);
 
-			loop {
+			stream.write(OPERATION_CREATED)?;
+		},
+		OPERATION_MSET => {
+			stream.read_exact(byte)?;
+
+			let count: u8 = byte[0];
+			let mut pairs: Vec<(String, String)> = Vec::with_capacity(count as usize);
+
+			for _ in 0..count {
+				pairs.push((read_key(stream, byte, session.is_key_size_16)?, read_string::<4>(stream, double_word)?));
+			}
+
+			if pairs.len() == 0 {
+				return Err(Box::from("count must be greater than 0"));
+			}
+
+			let mut guard: RwLockWriteGuard<'_, Cache> = cache.write()
+				.map_err(|error: PoisonError<RwLockWriteGuard<'_, Cache>>| error.to_string())?;
+			let storage_guard: RwLockWriteGuard<'_, Storage> = storage.write()
+				.map_err(|error: PoisonError<RwLockWriteGuard<'_, Storage>>| error.to_string())?;
+			let mut previous_values: Vec<(Option<Vec<u8>>, Option<u64>)> = Vec::with_capacity(pairs.len());
+
+			// storage goes first and the cache is only touched once every write landed, so a failure leaves both as they were
+			for (i, (key, value)) in pairs.iter().enumerate() {
 				if let Err(error) = (|| -> Result<()> {
-					stream.read_exact(&mut byte)?;
-
-					match &byte {
-						OPERATION_SET => {
-							let key: String = read_string::<1>(&mut stream, &mut byte)?;
-							let value: String = read_string::<4>(&mut stream, &mut double_word)?;
-
-							cache.lock()
-								.map_err(|error: PoisonError<MutexGuard<'_, Cache>>| error.to_string())?
-								.set(&key, Entry::new(&value)?)?;
-							storage.write()
-								.map_err(|error: PoisonError<RwLockWriteGuard<'_, Storage>>| error.to_string())?
-								.write(&key, value)?;
-
-							stream.write(OPERATION_OK)?;
-						},
-						OPERATION_DELETE => {
-							let key: String = read_string::<1>(&mut stream, &mut byte)?;
-
-							cache.lock()
-								.map_err(|error: PoisonError<MutexGuard<'_, Cache>>| error.to_string())?
-								.remove(&key);
-
-							if !storage.write()
-								.map_err(|error: PoisonError<RwLockWriteGuard<'_, Storage>>| error.to_string())?
-								.delete(&key)? {
-								return Err(Box::from("key must exist"));
-							}
+					previous_values.push((storage_guard.read(key)?, storage_guard.read_ttl(key)?));
+					storage_guard.delete_ttl(key)?;
+					storage_guard.write(key, value.as_bytes())?;
 
-							stream.write(OPERATION_OK)?;
-						},
-						OPERATION_GET => {
-							let key: String = read_string::<1>(&mut stream, &mut byte)?;
-							let (is_cached, value): (bool, String) = if let Some(entry) = cache.lock()
-								.map_err(|error: PoisonError<MutexGuard<'_, Cache>>| error.to_string())?
-								.get(&key)? {
-								(true, entry.value.clone())
+					Ok(())
+				})() {
+					// newest first, so a key repeated in the batch ends up with its original value
+					for ((key, _), (previous_value, previous_expires_at)) in zip(&pairs, &previous_values).rev() {
+						if let Err(error) = (|| -> Result<()> {
+							if let Some(previous_value) = previous_value {
+								storage_guard.write(key, previous_value)?;
 							} else {
-								if let Some(value) = storage.read()
-									.map_err(|error: PoisonError<RwLockReadGuard<'_, Storage>>| error.to_string())?
-									.read(&key)? {
-										(false, value)
-									} else {
-										return Err(Box::from("key must exist"));
-									}
-							};
-
-							if !is_cached {
-								cache.lock()
-									.map_err(|error: PoisonError<MutexGuard<'_, Cache>>| error.to_string())?
-									.set(&key, Entry::new(&value)?)?;
+								storage_guard.delete(key)?;
+							}
+
+							if let Some(previous_expires_at) = previous_expires_at {
+								storage_guard.write_ttl(key, *previous_expires_at)?;
 							}
 
-							let value_length: usize = value.len();
-
-							double_word[0] = (value_length >> 24) as u8;
-							double_word[1] = (value_length >> 16) as u8;
-							double_word[2] = (value_length >> 8) as u8;
-							double_word[3] = value_length as u8;
-
-							stream.write_vectored(&[
-								IoSlice::new(OPERATION_VALUE),
-								IoSlice::new(&double_word),
-								IoSlice::new(value.as_bytes())
-							])?;
-						},
-						OPERATION_NOOP => {
-							stream.write(OPERATION_OK)?;
-						},
-						OPERATION_QUIT => {
-							return Err(Box::from(""));
-						},
-						_ => {
-							return Err(Box::from("operation must be valid"));
+							Ok(())
+						})() {
+							warn!("{} while rolling back {:?}\n", error, key);
 						}
 					}
 
-					Ok(())
-				})() {
-					if let Some(error) = error.downcast_ref::<_Error>() {
-						let _ = send_error(&mut stream, &mut double_word, match error.kind() {
-							ErrorKind::UnexpectedEof => {
-								warn!("client terminated from {}\n", get_address(&stream));
-
-								break;
-							},
-							ErrorKind::StorageFull => "storage must have free space".to_owned(),
-							ErrorKind::WouldBlock | ErrorKind::TimedOut => "packet must be sent in time".to_owned(),
-							ErrorKind::OutOfMemory => "memory must have free space".to_owned(),
-							_ => error.to_string()
-						});
-						let _ = stream.write(OPERATION_QUIT);
+					return Err(Box::from(format!("key at index {} must be written: {}", i, error)));
+				}
+			}
 
-						break;
-					}
+			for (key, value) in pairs {
+				if let Err(error) = guard.set(&key, Entry::new(Value::String(value))?, None) {
+					// storage already holds the new value, so a stale entry must not shadow it
+					guard.remove(&key);
+					warn!("{} while caching {:?}\n", error, key);
+				}
 
-					let message: String = error.to_string();
+				guard.notify(&key, KeyspaceEvent::Set)?;
+			}
 
-					if message.len() == 0 {
-						info!("client disconnected from {}\n", get_address(&stream));
+			guard.mark_written();
+			drop(storage_guard);
+			drop(guard);
 
-						break;
-					}
+			stream.write(OPERATION_OK)?;
+		},
+		OPERATION_FLUSH => {
+			if !ARGUMENT.is_flush_allowed {
+				return Err(Box::from("flush not permitted, start the server with --allow-flush"));
+			}
 
-					if send_error(&mut stream, &mut double_word, message).is_err() {
-						break;
-					}
+			// both locks stay held so no write can land in between and survive the wipe
+			let mut guard: RwLockWriteGuard<'_, Cache> = cache.write()
+				.map_err(|error: PoisonError<RwLockWriteGuard<'_, Cache>>| error.to_string())?;
+			let mut storage_guard: RwLockWriteGuard<'_, Storage> = storage.write()
+				.map_err(|error: PoisonError<RwLockWriteGuard<'_, Storage>>| error.to_string())?;
+
+			// the cache goes first, so a wipe that fails halfway leaves only storage to read the survivors from
+			guard.clear();
+			guard.mark_written();
+			storage_guard.clear()?;
+			drop(storage_guard);
+			drop(guard);
+
+			stream.write(OPERATION_OK)?;
+		},
+		OPERATION_DELETE => {
+			let key: String = read_key(stream, byte, session.is_key_size_16)?;
+
+			if !remove(&cache, &storage, &key)? {
+				return Err(Box::from("key must exist"));
+			}
+
+			stream.write(OPERATION_OK)?;
+		},
+		OPERATION_MDEL => {
+			stream.read_exact(byte)?;
+
+			let count: u8 = byte[0];
+			let mut keys: Vec<String> = Vec::with_capacity(count as usize);
+
+			for _ in 0..count {
+				keys.push(read_key(stream, byte, session.is_key_size_16)?);
+			}
+
+			let mut guard: RwLockWriteGuard<'_, Cache> = cache.write()
+				.map_err(|error: PoisonError<RwLockWriteGuard<'_, Cache>>| error.to_string())?;
+			let storage_guard: RwLockWriteGuard<'_, Storage> = storage.write()
+				.map_err(|error: PoisonError<RwLockWriteGuard<'_, Storage>>| error.to_string())?;
+
+			// missing keys are skipped, the reply only counts what was actually there
+			byte[0] = 0;
+
+			for key in &keys {
+				let is_cached: bool = guard.remove(key);
+
+				if storage_guard.delete(key)? || is_cached {
+					byte[0] += 1;
+					guard.notify(key, KeyspaceEvent::Delete)?;
 				}
 			}
-		})?;
-	}
+
+			guard.mark_written();
+			drop(storage_guard);
+			drop(guard);
+
+			write_all_vectored(stream, &mut [
+				IoSlice::new(OPERATION_OK),
+				IoSlice::new(byte)
+			])?;
+		},
+		OPERATION_RENAME => {
+			let source: String = read_key(stream, byte, session.is_key_size_16)?;
+			let destination: String = read_key(stream, byte, session.is_key_size_16)?;
+
+			if source == destination {
+				stream.write(OPERATION_OK)?;
+
+				return Ok(());
+			}
+
+			// both locks stay held so no client sees the value under both keys or under neither
+			let mut guard: RwLockWriteGuard<'_, Cache> = cache.write()
+				.map_err(|error: PoisonError<RwLockWriteGuard<'_, Cache>>| error.to_string())?;
+			let storage_guard: RwLockWriteGuard<'_, Storage> = storage.write()
+				.map_err(|error: PoisonError<RwLockWriteGuard<'_, Storage>>| error.to_string())?;
+			let now: u64 = unix_epoch_millis()?;
+
+			// the lifetime travels with the value
+			let (value, ttl): (Value, Option<u64>) = if let Some(entry) = guard.get(&source)? {
+				(entry.value.clone(), entry.expires_at
+					.map(|expires_at: u64| expires_at.saturating_sub(now)))
+			} else if let Some(loaded) = load(&storage_guard, &source)? {
+				loaded
+			} else {
+				return Err(Box::from("key must exist"));
+			};
+
+			storage_guard.delete_ttl(&destination)?;
+			storage_guard.write(&destination, &value.to_bytes())?;
+
+			if let Some(ttl) = ttl {
+				storage_guard.write_ttl(&destination, now + ttl)?;
+			}
+
+			storage_guard.delete(&source)?;
+			guard.remove(&source);
+			guard.set(&destination, Entry::new(value)?, ttl)?;
+			guard.mark_written();
+			guard.notify(&source, KeyspaceEvent::Delete)?;
+			guard.notify(&destination, KeyspaceEvent::Set)?;
+			drop(storage_guard);
+			drop(guard);
+
+			stream.write(OPERATION_OK)?;
+		},
+		OPERATION_COPY => {
+			let source: String = read_key(stream, byte, session.is_key_size_16)?;
+			let destination: String = read_key(stream, byte, session.is_key_size_16)?;
+
+			stream.read_exact(byte)?;
+
+			if byte[0] & !0b1 != 0 {
+				return Err(Box::from("flags must only set replace"));
+			}
+
+			let is_replacing: bool = byte[0] & 0b1 != 0;
+
+			if source == destination {
+				return Err(Box::from("destination must differ from source"));
+			}
+
+			// both locks stay held so the copy matches the source as of a single moment
+			let mut guard: RwLockWriteGuard<'_, Cache> = cache.write()
+				.map_err(|error: PoisonError<RwLockWriteGuard<'_, Cache>>| error.to_string())?;
+			let storage_guard: RwLockWriteGuard<'_, Storage> = storage.write()
+				.map_err(|error: PoisonError<RwLockWriteGuard<'_, Storage>>| error.to_string())?;
+			let now: u64 = unix_epoch_millis()?;
+
+			if !is_replacing && (guard.contains(&destination)? || (storage_guard.exists(&destination)? && !storage_guard.read_ttl(&destination)?
+				.is_some_and(|expires_at: u64| expires_at <= now))) {
+				return Err(Box::from("destination must not exist"));
+			}
+
+			// the copy only gets whatever lifetime the source has left
+			let (value, ttl): (Value, Option<u64>) = if let Some(entry) = guard.get(&source)? {
+				(entry.value.clone(), entry.expires_at
+					.map(|expires_at: u64| expires_at.saturating_sub(now)))
+			} else if let Some(loaded) = load(&storage_guard, &source)? {
+				loaded
+			} else {
+				return Err(Box::from("key must exist"));
+			};
+
+			storage_guard.delete_ttl(&destination)?;
+			storage_guard.write(&destination, &value.to_bytes())?;
+
+			if let Some(ttl) = ttl {
+				storage_guard.write_ttl(&destination, now + ttl)?;
+			}
+
+			guard.set(&destination, Entry::new(value)?, ttl)?;
+			guard.mark_written();
+			guard.notify(&destination, KeyspaceEvent::Set)?;
+			drop(storage_guard);
+			drop(guard);
+
+			stream.write(OPERATION_OK)?;
+		},
+		OPERATION_GET => {
+			let key: String = read_key(stream, byte, session.is_key_size_16)?;
+			let value: String = if let Some(value) = fetch_string(&cache, &storage, &key)? {
+				value
+			} else {
+				return Err(Box::from("key must exist"));
+			};
+
+			send_value(stream, double_word, value.as_bytes())?;
+		},
+		OPERATION_STRLEN => {
+			let key: String = read_key(stream, byte, session.is_key_size_16)?;
+
+			// read the same way as GET, so the evictor sees the access, and 0 for a missing key
+			let length: usize = fetch_string(&cache, &storage, &key)?
+				.map(|value: String| value.len())
+				.unwrap_or(0);
+
+			send_value(stream, double_word, length.to_string().as_bytes())?;
+		},
+		OPERATION_GETDEL => {
+			let key: String = read_key(stream, byte, session.is_key_size_16)?;
+
+			// both locks stay held, so of two consumers racing for the key only one gets the value
+			let mut guard: RwLockWriteGuard<'_, Cache> = cache.write()
+				.map_err(|error: PoisonError<RwLockWriteGuard<'_, Cache>>| error.to_string())?;
+			let storage_guard: RwLockWriteGuard<'_, Storage> = storage.write()
+				.map_err(|error: PoisonError<RwLockWriteGuard<'_, Storage>>| error.to_string())?;
+			let now: u64 = unix_epoch_millis()?;
+			let value: Option<Value> = match guard.remove_returning(&key) {
+				Some(entry) if !entry.is_expired(now) => Some(entry.value),
+				_ => load(&storage_guard, &key)?
+					.map(|(value, _): (Value, Option<u64>)| value)
+			};
+			let value: String = match value {
+				Some(Value::String(value)) => value,
+				// a collection stays in storage and only loses its cached copy
+				Some(_) => return Err(Box::from("value must be a string")),
+				None => return Err(Box::from("key must exist"))
+			};
+
+			storage_guard.delete(&key)?;
+			guard.mark_written();
+			guard.notify(&key, KeyspaceEvent::Delete)?;
+			drop(storage_guard);
+			drop(guard);
+
+			send_value(stream, double_word, value.as_bytes())?;
+		},
+		OPERATION_GETEX_PERSIST => {
+			let key: String = read_key(stream, byte, session.is_key_size_16)?;
+
+			fetch(&cache, &storage, &key)?;
+
+			// one cache lock covers the read and the persist, so the value cannot expire between them
+			let mut guard: RwLockWriteGuard<'_, Cache> = cache.write()
+				.map_err(|error: PoisonError<RwLockWriteGuard<'_, Cache>>| error.to_string())?;
+			let value: String = match guard.get(&key)? {
+				Some(Entry { value: Value::String(value), .. }) => value.clone(),
+				Some(_) => return Err(Box::from("value must be a string")),
+				None => return Err(Box::from("key must exist"))
+			};
+
+			if guard.persist(&key)? {
+				storage.write()
+					.map_err(|error: PoisonError<RwLockWriteGuard<'_, Storage>>| error.to_string())?
+					.delete_ttl(&key)?;
+				guard.notify(&key, KeyspaceEvent::Persist)?;
+			}
+
+			drop(guard);
+
+			send_value(stream, double_word, value.as_bytes())?;
+		},
+		OPERATION_MGET => {
+			stream.read_exact(byte)?;
+
+			let count: u8 = byte[0];
+
+			if count == 0 {
+				return Err(Box::from("count must be greater than 0"));
+			}
+
+			let mut keys: Vec<String> = Vec::with_capacity(count as usize);
+
+			for _ in 0..count {
+				keys.push(read_key(stream, byte, session.is_key_size_16)?);
+			}
+
+			// one frame per key in request order, so a missing key cannot shift the ones after it
+			let mut frames: Vec<Vec<u8>> = Vec::with_capacity(keys.len());
+
+			for key in &keys {
+				let (operation, value): (&[u8; 1], Vec<u8>) = match fetch_string(&cache, &storage, key) {
+					Ok(Some(value)) => (OPERATION_VALUE, value.into_bytes()),
+					Ok(None) => (OPERATION_ERROR, b"key must exist".to_vec()),
+					Err(error) => (OPERATION_ERROR, error.to_string().into_bytes())
+				};
+				let mut frame: Vec<u8> = Vec::with_capacity(value.len() + 5);
+
+				frame.extend_from_slice(operation);
+				frame.extend_from_slice(&(value.len() as u32).to_be_bytes());
+				frame.extend(value);
+				frames.push(frame);
+			}
+
+			let mut buffers: Vec<IoSlice> = frames.iter()
+				.map(|frame: &Vec<u8>| IoSlice::new(frame))
+				.collect();
+
+			write_all_vectored(stream, &mut buffers)?;
+		},
+		OPERATION_EXISTS => {
+			let key: String = read_key(stream, byte, session.is_key_size_16)?;
+
+			// no promotion and no access bookkeeping, a probe must not look like a read to the evictor
+			let is_cached: bool = cache.read()
+				.map_err(|error: PoisonError<RwLockReadGuard<'_, Cache>>| error.to_string())?
+				.contains(&key)?;
+			let is_existing: bool = is_cached || {
+				let storage_guard: RwLockReadGuard<'_, Storage> = storage.read()
+					.map_err(|error: PoisonError<RwLockReadGuard<'_, Storage>>| error.to_string())?;
+				let now: u64 = unix_epoch_millis()?;
+
+				storage_guard.exists(&key)? && !storage_guard.read_ttl(&key)?
+					.is_some_and(|expires_at: u64| expires_at <= now)
+			};
+
+			stream.write(if is_existing {
+				OPERATION_OK
+			} else {
+				OPERATION_NOT_FOUND
+			})?;
+		},
+		OPERATION_EXPIRE | OPERATION_PEXPIRE => {
+			let key: String = read_key(stream, byte, session.is_key_size_16)?;
+			let mut quad_word: [u8; 8] = [0; 8];
+			let ttl: u64 = if operation == OPERATION_EXPIRE {
+				read_double_word(stream, double_word)? as u64 * 1000
+			} else {
+				read_quad_word(stream, &mut quad_word)?
+			};
+
+			// promote first so a key only storage holds can be given a lifetime as well
+			fetch(&cache, &storage, &key)?;
+
+			let mut guard: RwLockWriteGuard<'_, Cache> = cache.write()
+				.map_err(|error: PoisonError<RwLockWriteGuard<'_, Cache>>| error.to_string())?;
+			let expires_at: u64 = if let Some(expires_at) = guard.expire(&key, ttl)? {
+				expires_at
+			} else {
+				return Err(Box::from("key must exist"));
+			};
+
+			storage.write()
+				.map_err(|error: PoisonError<RwLockWriteGuard<'_, Storage>>| error.to_string())?
+				.write_ttl(&key, expires_at)?;
+			guard.notify(&key, KeyspaceEvent::Expire(ttl))?;
+			drop(guard);
+
+			stream.write(OPERATION_OK)?;
+		},
+		OPERATION_PERSIST => {
+			let key: String = read_key(stream, byte, session.is_key_size_16)?;
+
+			fetch(&cache, &storage, &key)?;
+
+			let mut guard: RwLockWriteGuard<'_, Cache> = cache.write()
+				.map_err(|error: PoisonError<RwLockWriteGuard<'_, Cache>>| error.to_string())?;
+
+			if !guard.contains(&key)? {
+				return Err(Box::from("key must exist"));
+			}
+
+			if !guard.persist(&key)? {
+				return Err(Box::from("key must have an expiry"));
+			}
+
+			// still under the cache lock, so an EXPIRE in between cannot have its sidecar removed
+			storage.write()
+				.map_err(|error: PoisonError<RwLockWriteGuard<'_, Storage>>| error.to_string())?
+				.delete_ttl(&key)?;
+			guard.notify(&key, KeyspaceEvent::Persist)?;
+			drop(guard);
+
+			stream.write(OPERATION_OK)?;
+		},
+		OPERATION_TTL | OPERATION_PTTL => {
+			let key: String = read_key(stream, byte, session.is_key_size_16)?;
+			let now: u64 = unix_epoch_millis()?;
+			let cached: Option<Option<u64>> = cache.read()
+				.map_err(|error: PoisonError<RwLockReadGuard<'_, Cache>>| error.to_string())?
+				.expiry(&key)?;
+			let expires_at: Option<u64> = if let Some(expires_at) = cached {
+				expires_at
+			} else {
+				let storage_guard: RwLockReadGuard<'_, Storage> = storage.read()
+					.map_err(|error: PoisonError<RwLockReadGuard<'_, Storage>>| error.to_string())?;
+				let expires_at: Option<u64> = storage_guard.read_ttl(&key)?;
+
+				if !storage_guard.exists(&key)? || expires_at.is_some_and(|expires_at: u64| expires_at <= now) {
+					return Err(Box::from("key must exist"));
+				}
+
+				expires_at
+			};
+
+			let remaining: Option<u64> = expires_at.map(|expires_at: u64| expires_at.saturating_sub(now));
+
+			// the maximum is reserved for a key without expiry, so real lifetimes stop just short of it
+			if operation == OPERATION_TTL {
+				write_all_vectored(stream, &mut [
+					IoSlice::new(OPERATION_DURATION),
+					IoSlice::new(&remaining.map(|remaining: u64| (remaining + 500) / 1000)
+						.map(|remaining: u64| remaining.min(u32::MAX as u64 - 1) as u32)
+						.unwrap_or(u32::MAX)
+						.to_be_bytes())
+				])?;
+			} else {
+				write_all_vectored(stream, &mut [
+					IoSlice::new(OPERATION_DURATION_MILLIS),
+					IoSlice::new(&remaining.map(|remaining: u64| remaining.min(u64::MAX - 1))
+						.unwrap_or(u64::MAX)
+						.to_be_bytes())
+				])?;
+			}
+		},
+		OPERATION_OBJECT_ENCODING => {
+			let key: String = read_key(stream, byte, session.is_key_size_16)?;
+			let encoding: &str = if let Some(value) = fetch(&cache, &storage, &key)? {
+				value.encoding()
+			} else {
+				return Err(Box::from("key must exist"));
+			};
+
+			send_value(stream, double_word, encoding.as_bytes())?;
+		},
+		OPERATION_LPUSH | OPERATION_RPUSH => {
+			let key: String = read_key(stream, byte, session.is_key_size_16)?;
+			let element: String = read_string::<4>(stream, double_word)?;
+			let is_front: bool = operation == OPERATION_LPUSH;
+			let length: usize = modify_list(&cache, &storage, &key, true, |list: &mut List| {
+				if is_front {
+					list.push_front(element);
+				} else {
+					list.push_back(element);
+				}
+
+				list.len()
+			})?.unwrap_or_default();
+
+			send_value(stream, double_word, length.to_string().as_bytes())?;
+		},
+		OPERATION_LPOP | OPERATION_RPOP => {
+			let key: String = read_key(stream, byte, session.is_key_size_16)?;
+			let is_front: bool = operation == OPERATION_LPOP;
+			let element: String = if let Some(Some(element)) = modify_list(&cache, &storage, &key, false, |list: &mut List| if is_front {
+				list.pop_front()
+			} else {
+				list.pop_back()
+			})? {
+				element
+			} else {
+				return Err(Box::from("key must exist"));
+			};
+
+			send_value(stream, double_word, element.as_bytes())?;
+		},
+		OPERATION_BLPOP | OPERATION_BRPOP => {
+			stream.read_exact(byte)?;
+
+			let count: u8 = byte[0];
+			let mut keys: Vec<String> = Vec::with_capacity(count as usize);
+
+			for _ in 0..count {
+				keys.push(read_key(stream, byte, session.is_key_size_16)?);
+			}
+
+			if keys.len() == 0 {
+				return Err(Box::from("count must be greater than 0"));
+			}
+
+			let timeout: Duration = Duration::from_millis(read_double_word(stream, double_word)? as u64);
+			let is_front: bool = operation == OPERATION_BLPOP;
+			let waited_at: Instant = Instant::now();
+			// subscribed before the first attempt, so a push landing in between still wakes this up
+			let subscription: Subscription<'_> = keyspace.subscribe_many(&keys)?;
+
+			let (key, element): (String, String) = 'waiting: loop {
+				// a client that went away while waiting must not take the element with it
+				if !stream.is_connected() {
+					return Err(Box::from(_Error::from(ErrorKind::UnexpectedEof)));
+				}
+
+				// the pop itself goes through modify_list, so of two waiters woken by one push only one gets it
+				for key in &keys {
+					if let Some(Some(element)) = modify_list(&cache, &storage, key, false, |list: &mut List| if is_front {
+						list.pop_front()
+					} else {
+						list.pop_back()
+					})? {
+						break 'waiting (key.clone(), element);
+					}
+				}
+
+				let waited_time: Duration = waited_at.elapsed();
+
+				if !timeout.is_zero() && waited_time >= timeout {
+					return Err(Box::from("list must receive an element before timeout"));
+				}
+
+				// waited in short slices, so the connection is looked at again even while nothing gets pushed
+				let event: Result<String, RecvTimeoutError> = subscription.receiver()
+					.recv_timeout(if timeout.is_zero() {
+						Duration::from_millis(100)
+					} else {
+						Duration::from_millis(100).min(timeout - waited_time)
+					});
+
+				match event {
+					Ok(_) | Err(RecvTimeoutError::Timeout) => {},
+					Err(RecvTimeoutError::Disconnected) => return Err(Box::from("keyspace must be available"))
+				}
+			};
+
+			drop(subscription);
+
+			// an element the client never got goes back where it came from
+			if let Err(error) = send_value(stream, double_word, key.as_bytes())
+				.and_then(|_: ()| send_value(stream, double_word, element.as_bytes())) {
+				modify_list(&cache, &storage, &key, true, |list: &mut List| if is_front {
+					list.push_front(element)
+				} else {
+					list.push_back(element)
+				})?;
+
+				return Err(error);
+			}
+		},
+		OPERATION_LLEN => {
+			let key: String = read_key(stream, byte, session.is_key_size_16)?;
+			let length: usize = match fetch(&cache, &storage, &key)? {
+				Some(Value::List(list)) => list.len(),
+				Some(_) => return Err(Box::from("value must be a list")),
+				None => 0
+			};
+
+			send_value(stream, double_word, length.to_string().as_bytes())?;
+		},
+		OPERATION_LRANGE => {
+			let key: String = read_key(stream, byte, session.is_key_size_16)?;
+			let start: i32 = read_double_word(stream, double_word)? as i32;
+			let end: i32 = read_double_word(stream, double_word)? as i32;
+			let elements: Vec<String> = match fetch(&cache, &storage, &key)? {
+				Some(Value::List(list)) => list.range(start, end),
+				Some(_) => return Err(Box::from("value must be a list")),
+				None => Vec::new()
+			};
+
+			send_values(stream, double_word, &elements)?;
+		},
+		OPERATION_LPOS => {
+			let key: String = read_key(stream, byte, session.is_key_size_16)?;
+			let element: String = read_string::<4>(stream, double_word)?;
+
+			stream.read_exact(byte)?;
+
+			// bit 0 announces a rank and bit 1 a max scan, each read only when present
+			let flags: u8 = byte[0];
+
+			if flags & !0b11 != 0 {
+				return Err(Box::from("flags must only set rank and max scan"));
+			}
+
+			let rank: usize = if flags & 0b01 != 0 {
+				stream.read_exact(byte)?;
+
+				if byte[0] == 0 {
+					return Err(Box::from("rank must be greater than 0"));
+				}
+
+				byte[0] as usize
+			} else {
+				1
+			};
+			let max_scan: usize = if flags & 0b10 != 0 {
+				read_double_word(stream, double_word)? as usize
+			} else {
+				0
+			};
+			let index: i64 = match fetch(&cache, &storage, &key)? {
+				Some(Value::List(list)) => list.position(&element, rank, max_scan)
+					.map(|index: usize| index as i64)
+					.unwrap_or(-1),
+				Some(_) => return Err(Box::from("value must be a list")),
+				None => -1
+			};
+
+			send_value(stream, double_word, index.to_string().as_bytes())?;
+		},
+		OPERATION_LINSERT => {
+			let key: String = read_key(stream, byte, session.is_key_size_16)?;
+
+			stream.read_exact(byte)?;
+
+			let is_after: bool = match byte[0] {
+				0 => false,
+				1 => true,
+				_ => return Err(Box::from("position must be 0 or 1"))
+			};
+			let pivot: String = read_string::<4>(stream, double_word)?;
+			let element: String = read_string::<4>(stream, double_word)?;
+
+			// -1 for a missing pivot and 0 for a missing key
+			let length: i64 = modify_list(&cache, &storage, &key, false, |list: &mut List| if list.insert(&pivot, element, is_after) {
+				list.len() as i64
+			} else {
+				-1
+			})?.unwrap_or_default();
+
+			send_value(stream, double_word, length.to_string().as_bytes())?;
+		},
+		OPERATION_HSET => {
+			let key: String = read_key(stream, byte, session.is_key_size_16)?;
+			let field: String = read_string::<1>(stream, byte)?;
+			let value: String = read_string::<4>(stream, double_word)?;
+
+			modify_hash(&cache, &storage, &key, true, |hash: &mut Hash| {
+				hash.insert(field, value);
+
+				Ok(())
+			})?;
+
+			stream.write(OPERATION_OK)?;
+		},
+		OPERATION_HGET => {
+			let key: String = read_key(stream, byte, session.is_key_size_16)?;
+			let field: String = read_string::<1>(stream, byte)?;
+			let value: String = match fetch(&cache, &storage, &key)? {
+				Some(Value::Hash(mut hash)) => if let Some(value) = hash.remove(&field) {
+					value
+				} else {
+					return Err(Box::from("field must exist"));
+				},
+				Some(_) => return Err(Box::from("value must be a hash")),
+				None => return Err(Box::from("key must exist"))
+			};
+
+			send_value(stream, double_word, value.as_bytes())?;
+		},
+		OPERATION_HDEL => {
+			let key: String = read_key(stream, byte, session.is_key_size_16)?;
+			let field: String = read_string::<1>(stream, byte)?;
+
+			if modify_hash(&cache, &storage, &key, false, |hash: &mut Hash| if hash.remove(&field).is_some() {
+				Ok(())
+			} else {
+				Err(Box::from("field must exist"))
+			})?.is_none() {
+				return Err(Box::from("key must exist"));
+			}
+
+			stream.write(OPERATION_OK)?;
+		},
+		OPERATION_HGETALL => {
+			let key: String = read_key(stream, byte, session.is_key_size_16)?;
+			let mut values: Vec<String> = Vec::new();
+
+			// flattened as field, value, field, value
+			match fetch(&cache, &storage, &key)? {
+				Some(Value::Hash(hash)) => for (field, value) in hash.pairs() {
+					values.push(field.clone());
+					values.push(value.clone());
+				},
+				Some(_) => return Err(Box::from("value must be a hash")),
+				None => ()
+			}
+
+			send_values(stream, double_word, &values)?;
+		},
+		OPERATION_HKEYS | OPERATION_HVALS => {
+			let key: String = read_key(stream, byte, session.is_key_size_16)?;
+			let values: Vec<String> = match fetch(&cache, &storage, &key)? {
+				Some(Value::Hash(hash)) => if operation == OPERATION_HKEYS {
+					hash.pairs()
+						.into_iter()
+						.map(|(field, _): (&String, &String)| field.clone())
+						.collect()
+				} else {
+					hash.pairs()
+						.into_iter()
+						.map(|(_, value): (&String, &String)| value.clone())
+						.collect()
+				},
+				Some(_) => return Err(Box::from("value must be a hash")),
+				None => Vec::new()
+			};
+
+			send_values(stream, double_word, &values)?;
+		},
+		OPERATION_HLEN => {
+			let key: String = read_key(stream, byte, session.is_key_size_16)?;
+			let length: usize = match fetch(&cache, &storage, &key)? {
+				Some(Value::Hash(hash)) => hash.len(),
+				Some(_) => return Err(Box::from("value must be a hash")),
+				None => 0
+			};
+
+			send_value(stream, double_word, length.to_string().as_bytes())?;
+		},
+		OPERATION_HEXISTS => {
+			let key: String = read_key(stream, byte, session.is_key_size_16)?;
+			let field: String = read_string::<1>(stream, byte)?;
+			let is_existing: bool = match fetch(&cache, &storage, &key)? {
+				Some(Value::Hash(hash)) => hash.get(&field).is_some(),
+				Some(_) => return Err(Box::from("value must be a hash")),
+				None => false
+			};
+
+			send_value(stream, double_word, if is_existing {
+				b"1"
+			} else {
+				b"0"
+			})?;
+		},
+		OPERATION_HRANDFIELD => {
+			let key: String = read_key(stream, byte, session.is_key_size_16)?;
+
+			stream.read_exact(byte)?;
+
+			// a negative count allows repeats
+			let count: i8 = byte[0] as i8;
+
+			stream.read_exact(byte)?;
+
+			let is_with_values: bool = match byte[0] {
+				0 => false,
+				1 => true,
+				_ => return Err(Box::from("with values must be 0 or 1"))
+			};
+			let mut values: Vec<String> = Vec::new();
+
+			match fetch(&cache, &storage, &key)? {
+				Some(Value::Hash(hash)) => for (field, value) in random_sample(hash.pairs(), count.unsigned_abs() as usize, count > 0) {
+					values.push(field.clone());
+
+					if is_with_values {
+						values.push(value.clone());
+					}
+				},
+				Some(_) => return Err(Box::from("value must be a hash")),
+				None => ()
+			}
+
+			send_values(stream, double_word, &values)?;
+		},
+		OPERATION_HMSET => {
+			let key: String = read_key(stream, byte, session.is_key_size_16)?;
+
+			stream.read_exact(byte)?;
+
+			let count: u8 = byte[0];
+			let mut pairs: Vec<(String, String)> = Vec::with_capacity(count as usize);
+
+			for _ in 0..count {
+				pairs.push((read_string::<1>(stream, byte)?, read_string::<4>(stream, double_word)?));
+			}
+
+			if pairs.len() == 0 {
+				return Err(Box::from("count must be greater than 0"));
+			}
+
+			modify_hash(&cache, &storage, &key, true, |hash: &mut Hash| {
+				for (field, value) in pairs {
+					hash.insert(field, value);
+				}
+
+				Ok(())
+			})?;
+
+			stream.write(OPERATION_OK)?;
+		},
+		OPERATION_HMGET => {
+			let key: String = read_key(stream, byte, session.is_key_size_16)?;
+
+			stream.read_exact(byte)?;
+
+			let count: u8 = byte[0];
+			let mut fields: Vec<String> = Vec::with_capacity(count as usize);
+
+			for _ in 0..count {
+				fields.push(read_string::<1>(stream, byte)?);
+			}
+
+			let hash: Hash = match fetch(&cache, &storage, &key)? {
+				Some(Value::Hash(hash)) => hash,
+				Some(_) => return Err(Box::from("value must be a hash")),
+				None => Hash::new()
+			};
+
+			// values are never empty, so an empty one marks a missing field
+			let values: Vec<String> = fields.iter()
+				.map(|field: &String| hash.get(field)
+					.cloned()
+					.unwrap_or_default())
+				.collect();
+
+			send_values(stream, double_word, &values)?;
+		},
+		OPERATION_HINCRBY | OPERATION_HINCRBYFLOAT => {
+			let key: String = read_key(stream, byte, session.is_key_size_16)?;
+			let field: String = read_string::<1>(stream, byte)?;
+			let mut quad_word: [u8; 8] = [0; 8];
+			let delta: u64 = read_quad_word(stream, &mut quad_word)?;
+			let is_float: bool = operation == OPERATION_HINCRBYFLOAT;
+
+			// a missing field counts as zero
+			let value: String = modify_hash(&cache, &storage, &key, true, |hash: &mut Hash| {
+				let current: &str = hash.get(&field)
+					.map(|value: &String| value.as_str())
+					.unwrap_or("0");
+				let value: String = if is_float {
+					let value: f64 = current.parse::<f64>()
+						.map_err(|_: ParseFloatError| "field must be a number")? + f64::from_bits(delta);
+
+					if !value.is_finite() {
+						return Err(Box::from("field must stay finite"));
+					}
+
+					value.to_string()
+				} else {
+					current.parse::<i64>()
+						.map_err(|_: ParseIntError| "field must be an integer")?
+						.checked_add(delta as i64)
+						.ok_or("field must not overflow")?
+						.to_string()
+				};
+
+				hash.insert(field, value.clone());
+
+				Ok(value)
+			})?.unwrap_or_default();
+
+			send_value(stream, double_word, value.as_bytes())?;
+		},
+		OPERATION_ZADD => {
+			let key: String = read_key(stream, byte, session.is_key_size_16)?;
+			let mut quad_word: [u8; 8] = [0; 8];
+			let score: f64 = f64::from_bits(read_quad_word(stream, &mut quad_word)?);
+			let member: String = read_string::<4>(stream, double_word)?;
+
+			if score.is_nan() {
+				return Err(Box::from("score must be a number"));
+			}
+
+			// 1 for a new member and 0 for a rescored one
+			let count: u8 = modify_sorted_set(&cache, &storage, &key, true, |sorted_set: &mut SortedSet| sorted_set.insert(member, score) as u8)?
+				.unwrap_or_default();
+
+			send_value(stream, double_word, count.to_string().as_bytes())?;
+		},
+		OPERATION_ZREM => {
+			let key: String = read_key(stream, byte, session.is_key_size_16)?;
+
+			stream.read_exact(byte)?;
+
+			let count: u8 = byte[0];
+			let mut members: Vec<String> = Vec::with_capacity(count as usize);
+
+			for _ in 0..count {
+				members.push(read_string::<4>(stream, double_word)?);
+			}
+
+			// members that were never there are skipped, the reply only counts what was actually removed
+			let count: usize = modify_sorted_set(&cache, &storage, &key, false, |sorted_set: &mut SortedSet| members.iter()
+				.filter(|member: &&String| sorted_set.remove(member))
+				.count())?
+				.unwrap_or_default();
+
+			send_value(stream, double_word, count.to_string().as_bytes())?;
+		},
+		OPERATION_ZPOPMIN | OPERATION_ZPOPMAX => {
+			let key: String = read_key(stream, byte, session.is_key_size_16)?;
+
+			stream.read_exact(byte)?;
+
+			let count: usize = byte[0] as usize;
+			let is_front: bool = operation == OPERATION_ZPOPMIN;
+			let mut values: Vec<String> = Vec::new();
+
+			// flattened as member, score, member, score
+			for (member, score) in modify_sorted_set(&cache, &storage, &key, false, |sorted_set: &mut SortedSet| sorted_set.pop(count, is_front))?
+				.unwrap_or_default() {
+				values.push(member);
+				values.push(score.to_string());
+			}
+
+			send_values(stream, double_word, &values)?;
+		},
+		OPERATION_ZRANGE => {
+			let key: String = read_key(stream, byte, session.is_key_size_16)?;
+			let start: i32 = read_double_word(stream, double_word)? as i32;
+			let end: i32 = read_double_word(stream, double_word)? as i32;
+			let members: Vec<String> = if let Some(sorted_set) = fetch_sorted_set(&cache, &storage, &key)? {
+				sorted_set.range(start, end)
+			} else {
+				Vec::new()
+			};
+
+			send_values(stream, double_word, &members)?;
+		},
+		OPERATION_ZUNIONSTORE | OPERATION_ZINTERSTORE => {
+			let destination: String = read_key(stream, byte, session.is_key_size_16)?;
+
+			stream.read_exact(byte)?;
+
+			let count: u8 = byte[0];
+			let mut keys: Vec<String> = Vec::with_capacity(count as usize);
+
+			for _ in 0..count {
+				keys.push(read_key(stream, byte, session.is_key_size_16)?);
+			}
+
+			stream.read_exact(byte)?;
+
+			let weight_count: u8 = byte[0];
+			let mut quad_word: [u8; 8] = [0; 8];
+			let mut weights: Vec<f64> = Vec::with_capacity(keys.len());
+
+			for _ in 0..weight_count {
+				weights.push(f64::from_bits(read_quad_word(stream, &mut quad_word)?));
+			}
+
+			if keys.len() == 0 {
+				return Err(Box::from("count must be greater than 0"));
+			}
+
+			// weights are all or nothing, an absent list weighs every source as 1
+			if weights.len() == 0 {
+				weights.resize(keys.len(), 1.0);
+			} else if weights.len() != keys.len() {
+				return Err(Box::from("weight count must be 0 or match key count"));
+			}
+
+			let is_union: bool = operation == OPERATION_ZUNIONSTORE;
+			let mut scores: HashMap<String, (f64, usize)> = HashMap::new();
+
+			// missing keys behave as empty sorted sets
+			for (key, weight) in zip(&keys, &weights) {
+				if let Some(sorted_set) = fetch_sorted_set(&cache, &storage, key)? {
+					for (member, score) in sorted_set.scores() {
+						let (sum, occurrences): &mut (f64, usize) = scores.entry(member.clone())
+							.or_insert((0.0, 0));
+
+						*sum += score * weight;
+						*occurrences += 1;
+					}
+				}
+			}
+
+			let mut result: SortedSet = SortedSet::new();
+
+			for (member, (sum, occurrences)) in scores {
+				// inf - inf has no meaningful sum, so it settles as 0
+				if is_union || occurrences == keys.len() {
+					result.insert(member, if sum.is_nan() {
+						0.0
+					} else {
+						sum
+					});
+				}
+			}
+
+			let count: usize = result.len();
+
+			if count == 0 {
+				remove(&cache, &storage, &destination)?;
+			} else {
+				store(&cache, &storage, &destination, Value::SortedSet(result))?;
+			}
+
+			send_value(stream, double_word, count.to_string().as_bytes())?;
+		},
+		OPERATION_ZDIFF | OPERATION_ZDIFFSTORE => {
+			let destination: Option<String> = if operation == OPERATION_ZDIFFSTORE {
+				Some(read_key(stream, byte, session.is_key_size_16)?)
+			} else {
+				None
+			};
+
+			stream.read_exact(byte)?;
+
+			let count: u8 = byte[0];
+			let mut keys: Vec<String> = Vec::with_capacity(count as usize);
+
+			for _ in 0..count {
+				keys.push(read_key(stream, byte, session.is_key_size_16)?);
+			}
+
+			if keys.len() == 0 {
+				return Err(Box::from("count must be greater than 0"));
+			}
+
+			// survivors keep their score from the first set, and missing keys behave as empty sorted sets
+			let mut result: SortedSet = fetch_sorted_set(&cache, &storage, &keys[0])?
+				.unwrap_or_else(SortedSet::new);
+
+			for key in &keys[1..] {
+				if result.len() == 0 {
+					break;
+				}
+
+				if let Some(sorted_set) = fetch_sorted_set(&cache, &storage, key)? {
+					let mut remaining: SortedSet = SortedSet::new();
+
+					for (member, score) in result.scores() {
+						if sorted_set.score(member).is_none() {
+							remaining.insert(member.clone(), *score);
+						}
+					}
+
+					result = remaining;
+				}
+			}
+
+			if let Some(destination) = destination {
+				let count: usize = result.len();
+
+				if count == 0 {
+					remove(&cache, &storage, &destination)?;
+				} else {
+					store(&cache, &storage, &destination, Value::SortedSet(result))?;
+				}
+
+				send_value(stream, double_word, count.to_string().as_bytes())?;
+			} else {
+				send_values(stream, double_word, &result.range(0, -1))?;
+			}
+		},
+		OPERATION_ZRANGEBYSCORE => {
+			let key: String = read_key(stream, byte, session.is_key_size_16)?;
+			let mut quad_word: [u8; 8] = [0; 8];
+			let minimum: f64 = f64::from_bits(read_quad_word(stream, &mut quad_word)?);
+			let maximum: f64 = f64::from_bits(read_quad_word(stream, &mut quad_word)?);
+			let limit: usize = read_double_word(stream, double_word)? as usize;
+
+			if minimum.is_nan() || maximum.is_nan() {
+				return Err(Box::from("score must be a number"));
+			}
+
+			let members: Vec<String> = if let Some(sorted_set) = fetch_sorted_set(&cache, &storage, &key)? {
+				sorted_set.range_by_score(minimum, maximum, limit)
+			} else {
+				Vec::new()
+			};
+
+			send_values(stream, double_word, &members)?;
+		},
+		OPERATION_ZRANGEBYLEX => {
+			let key: String = read_key(stream, byte, session.is_key_size_16)?;
+			let minimum: String = read_string::<1>(stream, byte)?;
+			let maximum: String = read_string::<1>(stream, byte)?;
+			let members: Vec<String> = if let Some(sorted_set) = fetch_sorted_set(&cache, &storage, &key)? {
+				sorted_set.range_by_lex(&minimum, &maximum)
+			} else {
+				Vec::new()
+			};
+
+			send_values(stream, double_word, &members)?;
+		},
+		OPERATION_ZRANK | OPERATION_ZSCORE => {
+			let key: String = read_key(stream, byte, session.is_key_size_16)?;
+			let member: String = read_string::<4>(stream, double_word)?;
+			let sorted_set: SortedSet = if let Some(sorted_set) = fetch_sorted_set(&cache, &storage, &key)? {
+				sorted_set
+			} else {
+				return Err(Box::from("key must exist"));
+			};
+			let value: String = if operation == OPERATION_ZRANK {
+				sorted_set.rank(&member)
+					.map(|rank: usize| rank.to_string())
+			} else {
+				sorted_set.score(&member)
+					.map(|score: f64| score.to_string())
+			}.ok_or("member must exist")?;
+
+			send_value(stream, double_word, value.as_bytes())?;
+		},
+		OPERATION_ZRANDMEMBER => {
+			let key: String = read_key(stream, byte, session.is_key_size_16)?;
+
+			stream.read_exact(byte)?;
+
+			// a negative count allows repeats
+			let count: i8 = byte[0] as i8;
+
+			stream.read_exact(byte)?;
+
+			let is_with_scores: bool = match byte[0] {
+				0 => false,
+				1 => true,
+				_ => return Err(Box::from("with scores must be 0 or 1"))
+			};
+			let mut values: Vec<String> = Vec::new();
+
+			if let Some(sorted_set) = fetch_sorted_set(&cache, &storage, &key)? {
+				for (member, score) in random_sample(sorted_set.scores().iter().collect(), count.unsigned_abs() as usize, count > 0) {
+					values.push(member.clone());
+
+					if is_with_scores {
+						values.push(score.to_string());
+					}
+				}
+			}
+
+			send_values(stream, double_word, &values)?;
+		},
+		OPERATION_KEYS => {
+			let pattern: String = read_string::<1>(stream, byte)?;
+			let mut keys: BTreeSet<String> = cache.read()
+				.map_err(|error: PoisonError<RwLockReadGuard<'_, Cache>>| error.to_string())?
+				.keys()
+				.filter(|key: &&String| is_glob_match(&pattern, key))
+				.cloned()
+				.collect();
+
+			// storage covers evicted keys, the set drops the ones both sides hold
+			keys.extend(storage.read()
+				.map_err(|error: PoisonError<RwLockReadGuard<'_, Storage>>| error.to_string())?
+				.list_keys()?
+				.into_iter()
+				.filter(|key: &String| is_glob_match(&pattern, key)));
+
+			// a u8 length cannot carry a longer key, so a client that old is not told about keys newer ones set
+			if !session.is_key_size_16 {
+				keys.retain(|key: &String| key.len() <= u8::MAX as usize);
+			}
+
+			if keys.len() > u16::MAX as usize {
+				return Err(Box::from(format!("pattern must match at most {} keys", u16::MAX)));
+			}
+
+			let mut response: Vec<u8> = Vec::with_capacity(3);
+
+			response.extend_from_slice(OPERATION_KEY_LIST);
+			response.extend_from_slice(&(keys.len() as u16).to_be_bytes());
+
+			for key in &keys {
+				if session.is_key_size_16 {
+					response.extend_from_slice(&(key.len() as u16).to_be_bytes());
+				} else {
+					response.push(key.len() as u8);
+				}
+
+				response.extend_from_slice(key.as_bytes());
+			}
+
+			stream.write_all(&response)?;
+		},
+		OPERATION_SCAN => {
+			let cursor: usize = read_double_word(stream, double_word)? as usize;
+
+			stream.read_exact(byte)?;
+
+			let count: usize = if byte[0] == 0 {
+				10
+			} else {
+				byte[0] as usize
+			};
+
+			// cursor 0 starts over with a snapshot of cached keys followed by the ones only storage holds
+			if cursor == 0 {
+				let cached_keys: Vec<String> = cache.read()
+					.map_err(|error: PoisonError<RwLockReadGuard<'_, Cache>>| error.to_string())?
+					.keys()
+					.cloned()
+					.collect();
+				let cached: HashSet<&String> = cached_keys.iter()
+					.collect();
+				let stored_keys: Vec<String> = storage.read()
+					.map_err(|error: PoisonError<RwLockReadGuard<'_, Storage>>| error.to_string())?
+					.list_keys()?
+					.into_iter()
+					.filter(|key: &String| !cached.contains(key))
+					.collect();
+
+				session.scan_keys = cached_keys;
+				session.scan_keys.extend(stored_keys);
+			} else if cursor > session.scan_keys.len() {
+				return Err(Box::from("cursor must come from a previous SCAN"));
+			}
+
+			let end: usize = (cursor + count).min(session.scan_keys.len());
+			let mut values: Vec<String> = Vec::with_capacity(end - cursor + 1);
+
+			// the next cursor leads the keys, and 0 once the snapshot is exhausted
+			values.push(if end == session.scan_keys.len() {
+				0
+			} else {
+				end
+			}.to_string());
+			values.extend_from_slice(&session.scan_keys[cursor..end]);
+
+			send_values(stream, double_word, &values)?;
+		},
+		OPERATION_SRANDMEMBER => {
+			let count: usize = read_double_word(stream, double_word)? as usize;
+
+			if count == 0 {
+				return Err(Box::from("count must be greater than 0"));
+			}
+
+			let keys: Vec<String> = cache.read()
+				.map_err(|error: PoisonError<RwLockReadGuard<'_, Cache>>| error.to_string())?
+				.random_keys(count);
+
+			send_values(stream, double_word, &keys)?;
+		},
+		OPERATION_RANDOMKEY => {
+			// every write goes through to storage, so its listing already covers the warm keys as well
+			let mut keys: Vec<String> = storage.read()
+				.map_err(|error: PoisonError<RwLockReadGuard<'_, Storage>>| error.to_string())?
+				.list_keys()?;
+
+			if keys.len() == 0 {
+				keys = cache.read()
+					.map_err(|error: PoisonError<RwLockReadGuard<'_, Cache>>| error.to_string())?
+					.random_keys(1);
+			}
+
+			if keys.len() == 0 {
+				return Err(Box::from("key must exist"));
+			}
+
+			let key: &String = &keys[(random() % keys.len() as u64) as usize];
+
+			send_value(stream, double_word, key.as_bytes())?;
+		},
+		OPERATION_SLOWLOG_GET => {
+			let count: usize = read_double_word(stream, double_word)? as usize;
+			let json: String = slow_log.lock()
+				.map_err(|error: PoisonError<MutexGuard<'_, SlowLog>>| error.to_string())?
+				.to_json(count);
+
+			send_value(stream, double_word, json.as_bytes())?;
+		},
+		OPERATION_SLOWLOG_LEN => {
+			let length: String = slow_log.lock()
+				.map_err(|error: PoisonError<MutexGuard<'_, SlowLog>>| error.to_string())?
+				.len()
+				.to_string();
+
+			send_value(stream, double_word, length.as_bytes())?;
+		},
+		OPERATION_SLOWLOG_RESET => {
+			slow_log.lock()
+				.map_err(|error: PoisonError<MutexGuard<'_, SlowLog>>| error.to_string())?
+				.reset();
+
+			stream.write(OPERATION_OK)?;
+		},
+		OPERATION_STATS => {
+			send_value(stream, double_word, get_stats(&telemetry).as_bytes())?;
+		},
+		OPERATION_SINTERSTORE | OPERATION_SUNIONSTORE | OPERATION_SDIFFSTORE => {
+			let set_operation: SetOperation = match operation {
+				OPERATION_SINTERSTORE => SetOperation::Intersection,
+				OPERATION_SUNIONSTORE => SetOperation::Union,
+				_ => SetOperation::Difference
+			};
+			let destination: String = read_key(stream, byte, session.is_key_size_16)?;
+			let first_key: String = read_key(stream, byte, session.is_key_size_16)?;
+			let second_key: String = read_key(stream, byte, session.is_key_size_16)?;
+
+			// missing keys behave as empty sets
+			let (count, value): (usize, String) = combine(set_operation, &fetch_string(&cache, &storage, &first_key)?.unwrap_or_default(), &fetch_string(&cache, &storage, &second_key)?.unwrap_or_default());
+
+			if count == 0 {
+				remove(&cache, &storage, &destination)?;
+			} else {
+				store(&cache, &storage, &destination, Value::String(value))?;
+			}
+
+			send_value(stream, double_word, count.to_string().as_bytes())?;
+		},
+		OPERATION_SCARD => {
+			let key: String = read_key(stream, byte, session.is_key_size_16)?;
+			let count: usize = if let Some(value) = fetch_string(&cache, &storage, &key)? {
+				parse(&value).1
+					.len()
+			} else {
+				0
+			};
+
+			send_value(stream, double_word, count.to_string().as_bytes())?;
+		},
+		OPERATION_CONFIG_VALIDATE => {
+			let name: String = read_string::<1>(stream, byte)?;
+			let value: String = read_string::<4>(stream, double_word)?;
+
+			// nothing is applied, the scratch argument is dropped right away
+			if let Err(error) = Argument::validate(&name, value) {
+				return Err(Box::from(format!("{} must be valid: {}", name, error)));
+			}
+
+			stream.write(OPERATION_OK)?;
+		},
+		OPERATION_CONFIG_SET => {
+			let name: String = read_string::<1>(stream, byte)?;
+			let value: String = read_string::<4>(stream, double_word)?;
+			let mut argument: Argument = Argument::defaults()?;
+
+			if let Err(error) = argument.apply(&name, value) {
+				return Err(Box::from(format!("{} must be valid: {}", name, error)));
+			}
+
+			// everything else is read once at startup, so only live settings are accepted
+			match name.as_str() {
+				"fsync-policy" => storage.write()
+					.map_err(|error: PoisonError<RwLockWriteGuard<'_, Storage>>| error.to_string())?
+					.set_fsync_policy(argument.fsync_policy),
+				_ => return Err(Box::from(format!("parameter {:?} must be settable at runtime", name)))
+			}
+
+			stream.write(OPERATION_OK)?;
+		},
+		OPERATION_CLIENT_SETNAME => {
+			let name: String = read_string::<1>(stream, byte)?;
+
+			// kept to something that reads cleanly in a log line
+			if name.len() > 64 || !name.bytes().all(|character: u8| character.is_ascii_alphanumeric() || character == b'-') {
+				return Err(Box::from("name must be at most 64 alphanumeric characters or hyphens"));
+			}
+
+			info!("client from {} named itself {}\n", stream.get_address(), name);
+
+			session.name = Some(name);
+
+			stream.write(OPERATION_OK)?;
+		},
+		OPERATION_CLIENT_GETNAME => {
+			if let Some(name) = &session.name {
+				send_value(stream, double_word, name.as_bytes())?;
+			} else {
+				stream.write(OPERATION_NOT_FOUND)?;
+			}
+		},
+		OPERATION_CLUSTER_MYID => {
+			send_value(stream, double_word, node_id.as_bytes())?;
+		},
+		OPERATION_COMMAND_COUNT => {
+			send_value(stream, double_word, (OPERATIONS.len() as u16).to_string().as_bytes())?;
+		},
+		OPERATION_COMMAND_GETKEYS => {
+			stream.read_exact(byte)?;
+
+			let frame_operation: [u8; 1] = *byte;
+			let length: u32 = read_double_word(stream, double_word)?;
+			let mut buffer: Vec<u8> = vec![0; length as usize];
+
+			stream.read_exact(&mut buffer)?;
+
+			// the frame is only parsed, so nothing it names is loaded, locked or counted
+			let keys: Vec<String> = match get_keys(&mut &buffer[..], &frame_operation, byte, double_word, session.is_key_size_16) {
+				Ok(keys) => keys,
+				Err(error) => {
+					if let Some(error) = error.downcast_ref::<_Error>() {
+						if error.kind() == ErrorKind::UnexpectedEof {
+							return Err(Box::from("frame must hold the whole operation"));
+						}
+					}
+
+					return Err(error);
+				}
+			};
+
+			send_values(stream, double_word, &keys)?;
+		},
+		OPERATION_SUBSCRIBE_STATS => {
+			let interval: u32 = read_double_word(stream, double_word)?;
+
+			if interval == 0 {
+				return Err(Box::from("interval must be greater than 0"));
+			}
+
+			// the read timeout doubles as the push interval until the client unsubscribes
+			stream.set_timeout(Duration::from_secs(interval as u64))?;
+
+			loop {
+				send_value(stream, double_word, get_stats(&telemetry).as_bytes())?;
+
+				match stream.read_exact(byte) {
+					Ok(()) => if byte == OPERATION_UNSUBSCRIBE_STATS {
+						break;
+					} else {
+						// the arguments of whatever was sent are unread, so the connection cannot recover
+						return Err(Box::from(_Error::new(ErrorKind::InvalidData, "operation must be UNSUBSCRIBE_STATS while subscribed")));
+					},
+					Err(error) if error.kind() == ErrorKind::WouldBlock || error.kind() == ErrorKind::TimedOut => continue,
+					Err(error) => return Err(Box::from(error))
+				}
+			}
+
+			stream.set_timeout(Duration::from_secs(ARGUMENT.read_timeout))?;
+			stream.write(OPERATION_OK)?;
+		},
+		OPERATION_UNSUBSCRIBE_STATS => {
+			return Err(Box::from("stats must be subscribed"));
+		},
+		OPERATION_SUBSCRIBE_KEYSPACE | OPERATION_SUBSCRIBE_KEYSPACE_PATTERN => {
+			// only a key takes the longer length, a pattern keeps u8
+			let key_or_pattern: String = if operation == OPERATION_SUBSCRIBE_KEYSPACE {
+				read_key(stream, byte, session.is_key_size_16)?
+			} else {
+				read_string::<1>(stream, byte)?
+			};
+			let subscription: Subscription<'_> = if operation == OPERATION_SUBSCRIBE_KEYSPACE {
+				keyspace.subscribe(&key_or_pattern)?
+			} else {
+				keyspace.subscribe_pattern(&key_or_pattern)?
+			};
+
+			// acknowledged up front so the client knows no event after this point can be missed
+			stream.write(OPERATION_OK)?;
+			// short reads keep the socket polled between events without delaying them much
+			stream.set_timeout(Duration::from_millis(10))?;
+
+			loop {
+				// whatever queued up is flushed in one go, so a busy key cannot keep the socket from being polled
+				let events: Vec<String> = match subscription.receiver().recv_timeout(Duration::from_millis(90)) {
+					Ok(event) => once(event).chain(subscription.receiver().try_iter())
+						.collect(),
+					Err(RecvTimeoutError::Timeout) => Vec::new(),
+					Err(RecvTimeoutError::Disconnected) => return Err(Box::from("keyspace must be available"))
+				};
+
+				for event in events {
+					write_all_vectored(stream, &mut [
+						IoSlice::new(OPERATION_KEYSPACE_EVENT),
+						IoSlice::new(&(event.len() as u32).to_be_bytes()),
+						IoSlice::new(event.as_bytes())
+					])?;
+				}
+
+				match stream.read_exact(byte) {
+					Ok(()) => if byte == OPERATION_UNSUBSCRIBE_KEYSPACE {
+						break;
+					} else {
+						// the arguments of whatever was sent are unread, so the connection cannot recover
+						return Err(Box::from(_Error::new(ErrorKind::InvalidData, "operation must be UNSUBSCRIBE_KEYSPACE while subscribed")));
+					},
+					Err(error) if error.kind() == ErrorKind::WouldBlock || error.kind() == ErrorKind::TimedOut => continue,
+					Err(error) => return Err(Box::from(error))
+				}
+			}
+
+			stream.set_timeout(Duration::from_secs(ARGUMENT.read_timeout))?;
+			stream.write(OPERATION_OK)?;
+		},
+		OPERATION_UNSUBSCRIBE_KEYSPACE => {
+			return Err(Box::from("keyspace must be subscribed"));
+		},
+		OPERATION_WAIT_QUIET => {
+			let period: Duration = Duration::from_millis(read_double_word(stream, double_word)? as u64);
+			let timeout: Duration = Duration::from_millis(read_double_word(stream, double_word)? as u64);
+			let waited_at: Instant = Instant::now();
+
+			loop {
+				let quiet_time: Duration = cache.read()
+					.map_err(|error: PoisonError<RwLockReadGuard<'_, Cache>>| error.to_string())?
+					.last_write_at()
+					.elapsed();
+
+				if quiet_time >= period {
+					break;
+				}
+
+				let waited_time: Duration = waited_at.elapsed();
+
+				if waited_time >= timeout {
+					return Err(Box::from("cache must become quiet before timeout"));
+				}
+
+				// a write while sleeping just pushes the next check further out
+				sleep((period - quiet_time).min(timeout - waited_time));
+			}
+
+			stream.write(OPERATION_OK)?;
+		},
+		OPERATION_WAIT_FOR_KEY => {
+			let key: String = read_key(stream, byte, session.is_key_size_16)?;
+			let timeout: Duration = Duration::from_millis(read_double_word(stream, double_word)? as u64);
+			let waited_at: Instant = Instant::now();
+			// subscribed before the first look, so a SET landing in between still wakes this up
+			let subscription: Subscription<'_> = keyspace.subscribe(&key)?;
+
+			let value: String = loop {
+				if let Some(value) = fetch_string(&cache, &storage, &key)? {
+					break value;
+				}
+
+				let waited_time: Duration = waited_at.elapsed();
+
+				if waited_time >= timeout {
+					return Err(Box::from("key must be created before timeout"));
+				}
+
+				// any event on the key is worth another look, a deletion just sends it back to waiting
+				match subscription.receiver().recv_timeout(timeout - waited_time) {
+					Ok(_) | Err(RecvTimeoutError::Timeout) => {},
+					Err(RecvTimeoutError::Disconnected) => return Err(Box::from("keyspace must be available"))
+				}
+			};
+
+			send_value(stream, double_word, value.as_bytes())?;
+		},
+		OPERATION_GETDEL_MANY => {
+			stream.read_exact(byte)?;
+
+			let count: u8 = byte[0];
+			let mut keys: Vec<String> = Vec::with_capacity(count as usize);
+
+			for _ in 0..count {
+				keys.push(read_key(stream, byte, session.is_key_size_16)?);
+			}
+
+			// both locks stay held across every key so no other client can claim any of them in between
+			let mut guard: RwLockWriteGuard<'_, Cache> = cache.write()
+				.map_err(|error: PoisonError<RwLockWriteGuard<'_, Cache>>| error.to_string())?;
+			let storage_guard: RwLockWriteGuard<'_, Storage> = storage.write()
+				.map_err(|error: PoisonError<RwLockWriteGuard<'_, Storage>>| error.to_string())?;
+			let mut values: Vec<String> = Vec::with_capacity(keys.len());
+
+			// every value is read before anything is removed, so a list among the keys leaves all of them in place
+			for key in &keys {
+				let value: Option<Value> = if let Some(entry) = guard.get(key)? {
+					Some(entry.value.clone())
+				} else {
+					load(&storage_guard, key)?
+						.map(|(value, _): (Value, Option<u64>)| value)
+				};
+
+				// values are never empty, so an empty one marks a missing key
+				values.push(match value {
+					Some(Value::String(value)) => value,
+					Some(_) => return Err(Box::from("value must be a string")),
+					None => String::new()
+				});
+			}
+
+			for (key, value) in zip(&keys, &values) {
+				guard.remove(key);
+				storage_guard.delete(key)?;
+
+				if value.len() != 0 {
+					guard.notify(key, KeyspaceEvent::Delete)?;
+				}
+			}
+
+			guard.mark_written();
+			drop(storage_guard);
+			drop(guard);
+
+			send_values(stream, double_word, &values)?;
+		},
+		OPERATION_WATCH => {
+			let key: String = read_key(stream, byte, session.is_key_size_16)?;
+
+			// promote first so the revision belongs to the value a later GET would see
+			fetch(&cache, &storage, &key)?;
+
+			let mut guard: RwLockWriteGuard<'_, Cache> = cache.write()
+				.map_err(|error: PoisonError<RwLockWriteGuard<'_, Cache>>| error.to_string())?;
+
+			// watching again only refreshes the revision, the session still holds a single watch on the key
+			if session.watches.contains_key(&key) {
+				guard.unwatch(&key);
+			}
+
+			let revision: u64 = guard.watch(&key);
+
+			drop(guard);
+
+			session.watches.insert(key, revision);
+
+			stream.write(OPERATION_OK)?;
+		},
+		OPERATION_EXEC_IF_UNCHANGED => {
+			let key: String = read_key(stream, byte, session.is_key_size_16)?;
+			let length: u32 = read_double_word(stream, double_word)?;
+			let mut buffer: Vec<u8> = vec![0; length as usize];
+
+			// the frame is read whole so a rejected operation leaves nothing unread on the connection
+			stream.read_exact(&mut buffer)?;
+
+			let revision: u64 = if let Some(revision) = session.watches.remove(&key) {
+				revision
+			} else {
+				return Err(Box::from("key must be watched"));
+			};
+			let _gate: RwLockWriteGuard<'_, ()> = gate.write()
+				.map_err(|error: PoisonError<RwLockWriteGuard<'_, ()>>| error.to_string())?;
+			let mut guard: RwLockWriteGuard<'_, Cache> = cache.write()
+				.map_err(|error: PoisonError<RwLockWriteGuard<'_, Cache>>| error.to_string())?;
+			let is_unchanged: bool = guard.revision(&key) == revision;
+
+			// the watch is spent whatever happens to the frame, so its revision is released right away
+			guard.unwatch(&key);
+
+			drop(guard);
+
+			if buffer.len() == 0 {
+				return Err(Box::from("frame must not be empty"));
+			}
+
+			let frame_operation: [u8; 1] = [buffer[0]];
+
+			if UNGATED_OPERATIONS.contains(&&frame_operation) {
+				return Err(Box::from(format!("{} must not be executed conditionally", operation_name(frame_operation[0]))));
+			}
+
+			if !is_unchanged {
+				return Err(Box::from("watched key must be unchanged"));
+			}
+
+			let mut frame: Frame = Frame {
+				buffer: &buffer[1..],
+				stream: stream
+			};
+
+			// running out of frame is the client's mistake, not a dropped connection
+			if let Err(error) = execute(&mut frame, &frame_operation, byte, double_word, context, session) {
+				if let Some(error) = error.downcast_ref::<_Error>() {
+					if error.kind() == ErrorKind::UnexpectedEof {
+						return Err(Box::from("frame must hold the whole operation"));
+					}
+				}
+
+				return Err(error);
+			}
+		},
+		OPERATION_NOOP => {
+			stream.write(OPERATION_OK)?;
+		},
+		OPERATION_PING => {
+			let length: u32 = read_double_word(stream, double_word)?;
+			let mut payload: Vec<u8> = vec![0; length as usize];
+
+			// read raw rather than through read_string, since an empty payload is allowed and need not be UTF-8
+			stream.read_exact(&mut payload)?;
+
+			write_all_vectored(stream, &mut [
+				IoSlice::new(OPERATION_PONG),
+				IoSlice::new(&length.to_be_bytes()),
+				IoSlice::new(&payload)
+			])?;
+		},
+		OPERATION_INFO => {
+			let (cache_used_entries, cache_capacity): (usize, usize) = {
+				let guard: RwLockReadGuard<'_, Cache> = cache.read()
+					.map_err(|error: PoisonError<RwLockReadGuard<'_, Cache>>| error.to_string())?;
+
+				(guard.len(), guard.capacity())
+			};
+			let storage_key_count: usize = storage.read()
+				.map_err(|error: PoisonError<RwLockReadGuard<'_, Storage>>| error.to_string())?
+				.list_keys()?
+				.len();
+			// taken last and alone, since the cache takes this lock while holding its own
+			let stats: MutexGuard<'_, Stats> = stats.lock()
+				.map_err(|error: PoisonError<MutexGuard<'_, Stats>>| error.to_string())?;
+
+			send_value(stream, double_word, format!("version:{}\nuptime_seconds:{}\nconnected_clients:{}\ncache_used_entries:{}\ncache_capacity:{}\ncache_hit_count:{}\ncache_miss_count:{}\neviction_count:{}\nstorage_key_count:{}\ntotal_commands_processed:{}\n", ARGUMENT.version, stats.uptime(), stats.connected_clients(), cache_used_entries, cache_capacity, stats.cache_hit_count(), stats.cache_miss_count(), stats.eviction_count(), storage_key_count, stats.total_commands_processed()).as_bytes())?;
+		},
+		OPERATION_QUIT => {
+			return Err(Box::from(""));
+		},
+		_ => {
+			return Err(Box::from("operation must be valid"));
+		}
+	}
+
+	Ok(())
+}
+
+fn handle<S: Stream>(mut stream: S, address: &str, context: Context) {
+	let mut double_word: [u8; 4] = [0; 4];
+
+	let version: Version = match (|| -> Result<Version> {
+		stream.write_vectored(&[
+			IoSlice::new(OPERATION_READY),
+			IoSlice::new(&ARGUMENT.version.as_bytes())
+		])?;
+
+		// 중략
+		stream.read_exact(&mut double_word)?;
+
+		if double_word[0] != OPERATION_HELLO[0] {
+			return Err(Box::from("handshake must start with HELLO operation"));
+		}
+
+		OPERATION_COUNTS[OPERATION_HELLO[0] as usize].fetch_add(1, AtomicOrdering::Relaxed);
+
+		let version: Version = if let Ok(version) = Version::try_from(&double_word[1..4]) {
+			if version > ARGUMENT.version {
+				return Err(Box::from(format!("client version must be less than or equal to {}", ARGUMENT.version)));
+			}
+
+			info!("client connected with {} from {} via {}\n", version, stream.get_address(), address);
+
+			version
+		} else {
+			return Err(Box::from("client version must be invalid\n"));
+		};
+
+		stream.set_timeout(Duration::from_secs(ARGUMENT.read_timeout))?;
+		stream.write(OPERATION_OK)?;
+
+		Ok(version)
+	})() {
+		Ok(version) => version,
+		Err(error) => {
+			let _ = send_error(&mut stream, &mut double_word, error.to_string());
+
+			return;
+		}
+	};
+
+	if let Ok(mut stats) = context.stats.lock() {
+		stats.connect();
+	}
+
+	let mut byte: [u8; 1] = [0];
+	let mut session: Session = Session {
+		watches: HashMap::new(),
+		scan_keys: Vec::new(),
+		name: None,
+		is_key_size_16: version >= KEY_SIZE_16
+	};
+
+	loop {
+		if let Err(error) = (|| -> Result<()> {
+			stream.read_exact(&mut byte)?;
+
+			let started_at: Instant = Instant::now();
+
+			let operation: [u8; 1] = byte;
+
+			OPERATION_COUNTS[operation[0] as usize].fetch_add(1, AtomicOrdering::Relaxed);
+			context.stats.lock()
+				.map_err(|error: PoisonError<MutexGuard<'_, Stats>>| error.to_string())?
+				.process();
+
+			if UNGATED_OPERATIONS.contains(&&operation) {
+				execute(&mut stream, &operation, &mut byte, &mut double_word, &context, &mut session)?;
+			} else {
+				let buffer: Vec<u8> = read_frame(&mut stream, &operation, &mut byte, &mut double_word, session.is_key_size_16)?;
+				// EXEC_IF_UNCHANGED takes this exclusively, so nothing runs between its check and its operation
+				let _gate: RwLockReadGuard<'_, ()> = context.gate.read()
+					.map_err(|error: PoisonError<RwLockReadGuard<'_, ()>>| error.to_string())?;
+				let mut frame: Frame = Frame {
+					buffer: &buffer,
+					stream: &mut stream
+				};
+
+				execute(&mut frame, &operation, &mut byte, &mut double_word, &context, &mut session)?;
+			}
+
+			let duration: u64 = started_at.elapsed().as_micros() as u64;
+
+			if duration >= ARGUMENT.slowlog_slower_than {
+				context.slow_log.lock()
+					.map_err(|error: PoisonError<MutexGuard<'_, SlowLog>>| error.to_string())?
+					.push(duration, operation_name(operation[0]), session.describe(stream.get_address()))?;
+			}
+
+			Ok(())
+		})() {
+			if let Some(error) = error.downcast_ref::<_Error>() {
+				let _ = send_error(&mut stream, &mut double_word, match error.kind() {
+					ErrorKind::UnexpectedEof => {
+						warn!("client terminated from {}\n", session.describe(stream.get_address()));
+
+						break;
+					},
+					ErrorKind::StorageFull => "storage must have free space".to_owned(),
+					ErrorKind::WouldBlock | ErrorKind::TimedOut => "packet must be sent in time".to_owned(),
+					ErrorKind::OutOfMemory => "memory must have free space".to_owned(),
+					_ => error.to_string()
+				});
+				let _ = stream.write(OPERATION_QUIT);
+
+				break;
+			}
+
+			let message: String = error.to_string();
+
+			if message.len() == 0 {
+				info!("client disconnected from {}\n", session.describe(stream.get_address()));
+
+				break;
+			}
+
+			if send_error(&mut stream, &mut double_word, message).is_err() {
+				break;
+			}
+		}
+	}
+
+	// watches left behind by the session would otherwise keep their revisions forever
+	if let Ok(mut cache) = context.cache.write() {
+		for key in session.watches.keys() {
+			cache.unwatch(key);
+		}
+	}
+
+	if let Ok(mut stats) = context.stats.lock() {
+		stats.disconnect();
+	}
+}
+
+fn accept<S: Stream + 'static>(incoming: impl Iterator<Item = IoResult<S>>, address: String, thread_pool: Arc<ThreadPool>, context: Context) -> Result<(), String> {
+	for stream in incoming {
+		let stream: S = stream.map_err(|error: _Error| format!("{} on {}", error, address))?;
+		let address: String = address.clone();
+		let context: Context = context.clone();
+
+		// a client that never says HELLO must not hold a thread for the full read timeout
+		stream.set_timeout(Duration::from_secs(ARGUMENT.handshake_timeout))
+			.map_err(|error: Box<dyn Error>| error.to_string())?;
+		stream.set_no_delay()
+			.map_err(|error: Box<dyn Error>| error.to_string())?;
+
+		thread_pool.execute(move || handle(stream, &address, context))
+			.map_err(|error: SendError<Task>| error.to_string())?;
+	}
+
+	Ok(())
+}
+
+pub fn serve() -> Result<()> {
+	info!("starting dQache {} on {}\n", ARGUMENT.version, ARGUMENT.platform);
+
+	let thread_pool: Arc<ThreadPool> = Arc::new(ThreadPool::new(ARGUMENT.thread_count)?);
+	let keyspace: Arc<Keyspace> = Arc::new(Keyspace::new());
+	let stats: Arc<Mutex<Stats>> = Arc::new(Mutex::new(Stats::new()));
+	let context: Context = Context {
+		cache: Arc::new(RwLock::new(Cache::new(ARGUMENT.model, ARGUMENT.capacity, keyspace.clone(), stats.clone())?)),
+		storage: Arc::new(RwLock::new(Storage::new(&ARGUMENT.directory, &ARGUMENT.storage_mirrors, ARGUMENT.fsync_policy)?)),
+		slow_log: Arc::new(Mutex::new(SlowLog::new(ARGUMENT.slowlog_max_len as usize))),
+		telemetry: thread_pool.telemetry(),
+		node_id: Arc::new(load_node_id(&ARGUMENT.directory)?),
+		gate: Arc::new(RwLock::new(())),
+		keyspace: keyspace,
+		stats: stats
+	};
+
+	info!("running as node {}\n", context.node_id);
+
+	let storage: Arc<RwLock<Storage>> = context.storage.clone();
+
+	// always running, since CONFIG_SET may switch to everysec at any time
+	spawn(move || loop {
+		sleep(Duration::from_secs(1));
+
+		if let Err(error) = (|| -> Result<()> {
+			storage.read()
+				.map_err(|error: PoisonError<RwLockReadGuard<'_, Storage>>| error.to_string())?
+				.sync_dirty_files()
+		})() {
+			warn!("{} while syncing storage\n", error);
+		}
+	});
+
+	let is_stopped: Arc<AtomicBool> = Arc::new(AtomicBool::new(false));
+	let expirer: JoinHandle<()> = {
+		let cache: Arc<RwLock<Cache>> = context.cache.clone();
+		let storage: Arc<RwLock<Storage>> = context.storage.clone();
+		let is_stopped: Arc<AtomicBool> = is_stopped.clone();
+
+		// dead entries would otherwise hold cache slots and storage files until someone reads them
+		spawn(move || while !is_stopped.load(AtomicOrdering::Relaxed) {
+			sleep(Duration::from_millis(ARGUMENT.expire_interval));
+
+			if let Err(error) = expire(&cache, &storage) {
+				warn!("{} while expiring keys\n", error);
+			}
+		})
+	};
+
+	if ARGUMENT.retrain_interval != 0 {
+		let cache: Arc<RwLock<Cache>> = context.cache.clone();
+
+		// the script may run for a long while, so it gets a thread of its own instead of holding up the listeners
+		spawn(move || loop {
+			sleep(Duration::from_secs(ARGUMENT.retrain_interval.saturating_mul(3600)));
+
+			if let Err(error) = retrain(&cache) {
+				warn!("{} while retraining model\n", error);
+			}
+		});
+	}
+
+	let mut listeners: Vec<JoinHandle<Result<(), String>>> = Vec::with_capacity(ARGUMENT.binds.len());
+
+	for bind in &ARGUMENT.binds {
+		let address: String = bind.to_string();
+		let thread_pool: Arc<ThreadPool> = thread_pool.clone();
+		let context: Context = context.clone();
+
+		listeners.push(match bind {
+			Bind::Tcp(socket_address) => {
+				let listener: TcpListener = TcpListener::bind(socket_address)?;
+
+				spawn(move || accept(listener.incoming(), address, thread_pool, context))
+			},
+			#[cfg(unix)]
+			Bind::Unix(path) => {
+				// a socket file left over from an unclean shutdown would make bind fail
+				if let Ok(metadata) = metadata(path) {
+					if metadata.file_type().is_socket() {
+						remove_file(path)?;
+					}
+				}
+
+				let listener: UnixListener = UnixListener::bind(path)?;
+
+				spawn(move || accept(listener.incoming(), address, thread_pool, context))
+			}
+		});
+
+		info!("listening on {}\n", bind);
+	}
+
+	info!("serving with {} threads\n", ARGUMENT.thread_count);
+
+	for listener in listeners {
+		listener.join()
+			.map_err(|_| "listener thread must not panic")??;
+	}
+
+	is_stopped.store(true, AtomicOrdering::Relaxed);
+	expirer.join()
+		.map_err(|_| "expiry thread must not panic")?;
 
 	Ok(())
 }
\ No newline at end of file
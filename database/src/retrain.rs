@@ -0,0 +1,152 @@
+use std::{
+	collections::{BTreeMap, HashMap, VecDeque},
+	env::temp_dir,
+	fs::{create_dir_all, write},
+	path::PathBuf,
+	process::{Command, ExitStatus, id},
+	sync::{Arc, PoisonError, RwLock, RwLockWriteGuard}
+};
+use crate::{
+	cache::Cache,
+	common::{ARGUMENT, Result, unix_epoch},
+	debug,
+	info,
+	model::DeepQNetwork
+};
+
+// enough for hours of steady eviction without the buffer growing with the uptime
+const MAXIMUM_OBSERVATION_COUNT: usize = 65536;
+
+pub struct Observation {
+	pub features: [f32; 4],
+	pub is_good: bool
+}
+
+pub struct Observations {
+	// evictions whose verdict is still open, keyed by the evicted key with its features, when it left and its place in order
+	pending: HashMap<String, ([f32; 4], u64, u64)>,
+	// the same keys oldest first, so both settling and the cap only ever look at the front
+	order: BTreeMap<u64, String>,
+	next_order: u64,
+	settled: VecDeque<Observation>,
+	lookback: u64
+}
+
+impl Observations {
+	pub fn new(lookback: u64) -> Observations {
+		Observations {
+			pending: HashMap::new(),
+			order: BTreeMap::new(),
+			next_order: 0,
+			settled: VecDeque::new(),
+			lookback: lookback
+		}
+	}
+
+	fn settle(self: &mut Self, features: [f32; 4], is_good: bool) {
+		if self.settled.len() == MAXIMUM_OBSERVATION_COUNT {
+			self.settled.pop_front();
+		}
+
+		self.settled.push_back(Observation {
+			features: features,
+			is_good: is_good
+		});
+	}
+
+	// only a key in the cache can be evicted, and coming back in already took it out of pending
+	pub fn evict(self: &mut Self, key: &str, features: [f32; 4], now: u64) {
+		if self.pending.len() == MAXIMUM_OBSERVATION_COUNT {
+			if let Some((_, oldest_key)) = self.order.pop_first() {
+				self.pending.remove(&oldest_key);
+			}
+		}
+
+		self.pending.insert(key.to_owned(), (features, now, self.next_order));
+		self.order.insert(self.next_order, key.to_owned());
+		self.next_order += 1;
+	}
+
+	// a key coming back into the cache within the lookback means its eviction was a bad call
+	pub fn request(self: &mut Self, key: &str, now: u64) {
+		if let Some((features, evicted_at, order)) = self.pending.remove(key) {
+			self.order.remove(&order);
+			self.settle(features, now.saturating_sub(evicted_at) > self.lookback);
+		}
+	}
+
+	pub fn drain(self: &mut Self, now: u64) -> Vec<Observation> {
+		while let Some(entry) = self.order.first_entry() {
+			let is_due: bool = self.pending.get(entry.get())
+				.is_some_and(|(_, evicted_at, _): &([f32; 4], u64, u64)| now.saturating_sub(*evicted_at) > self.lookback);
+
+			if !is_due {
+				break;
+			}
+
+			if let Some((features, _, _)) = self.pending.remove(&entry.remove()) {
+				self.settle(features, true);
+			}
+		}
+
+		self.settled.drain(..)
+			.collect()
+	}
+}
+
+// the script is handed the observations as csv and the path it must write the new onnx model to
+pub fn retrain(cache: &Arc<RwLock<Cache>>) -> Result<()> {
+	let script: String = if let Some(script) = ARGUMENT.retrain_script.clone() {
+		script
+	} else {
+		return Err(Box::from("retrain script must be provided"));
+	};
+	let observations: Vec<Observation> = cache.write()
+		.map_err(|error: PoisonError<RwLockWriteGuard<'_, Cache>>| error.to_string())?
+		.drain_observations(unix_epoch()?);
+
+	if observations.len() == 0 {
+		debug!("skipped retraining without observations\n");
+
+		return Ok(());
+	}
+
+	let directory: PathBuf = temp_dir().join(format!("dqache-{}", id()));
+	let observations_path: PathBuf = directory.join("observations.csv");
+	let model_path: PathBuf = directory.join("model.onnx");
+	let mut data: String = String::from("elapsed,access_count,size,capacity,is_good\n");
+
+	for observation in &observations {
+		data.push_str(&format!("{},{},{},{},{}\n", observation.features[0], observation.features[1], observation.features[2], observation.features[3], observation.is_good as u8));
+	}
+
+	create_dir_all(&directory)?;
+	write(&observations_path, data)?;
+
+	info!("retraining model on {} observations with {}\n", observations.len(), script);
+
+	let status: ExitStatus = Command::new(if cfg!(target_os = "windows") {
+		"python"
+	} else {
+		"python3"
+	})
+		.arg(&script)
+		.arg(&observations_path)
+		.arg(&model_path)
+		.status()?;
+
+	if !status.success() {
+		return Err(Box::from(format!("retrain script must exit successfully, not with {}", status)));
+	}
+
+	// built before taking the lock, so clients keep being served while the session loads
+	let model: DeepQNetwork = DeepQNetwork::from_file(&model_path)?;
+
+	cache.write()
+		.map_err(|error: PoisonError<RwLockWriteGuard<'_, Cache>>| error.to_string())?
+		.set_model(Box::new(model));
+
+	info!("reloaded model from {}\n", model_path.display());
+
+	Ok(())
+}
\ No newline at end of file
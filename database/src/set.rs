@@ -0,0 +1,56 @@
+use std::collections::HashSet;
+
+pub enum SetOperation {
+	Intersection,
+	Union,
+	Difference
+}
+
+// members are separated by newlines if there is any, otherwise by commas
+pub fn parse(value: &str) -> (char, Vec<&str>) {
+	let delimiter: char = if value.contains('\n') {
+		'\n'
+	} else {
+		','
+	};
+	let mut members: Vec<&str> = Vec::new();
+	let mut seen: HashSet<&str> = HashSet::new();
+
+	for member in value.split(delimiter) {
+		if member.len() != 0 && seen.insert(member) {
+			members.push(member);
+		}
+	}
+
+	(delimiter, members)
+}
+
+pub fn combine(operation: SetOperation, first: &str, second: &str) -> (usize, String) {
+	let (first_delimiter, first_members): (char, Vec<&str>) = parse(first);
+	let (second_delimiter, second_members): (char, Vec<&str>) = parse(second);
+	let first_set: HashSet<&str> = first_members.iter()
+		.copied()
+		.collect::<HashSet<&str>>();
+	let second_set: HashSet<&str> = second_members.iter()
+		.copied()
+		.collect::<HashSet<&str>>();
+
+	let members: Vec<&str> = match operation {
+		SetOperation::Intersection => first_members.into_iter()
+			.filter(|member: &&str| second_set.contains(member))
+			.collect::<Vec<&str>>(),
+		SetOperation::Union => first_members.into_iter()
+			.chain(second_members.into_iter()
+				.filter(|member: &&str| !first_set.contains(member)))
+			.collect::<Vec<&str>>(),
+		SetOperation::Difference => first_members.into_iter()
+			.filter(|member: &&str| !second_set.contains(member))
+			.collect::<Vec<&str>>()
+	};
+
+	(members.len(), members.join(if first_delimiter == '\n' || second_delimiter == '\n' {
+		"\n"
+	} else {
+		","
+	}))
+}
\ No newline at end of file
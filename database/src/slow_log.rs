@@ -0,0 +1,79 @@
+use std::collections::VecDeque;
+use crate::{
+	common::{ARGUMENT, Result, escape_json, unix_epoch},
+	debug
+};
+
+pub struct SlowLogEntry {
+	pub id: u64,
+	pub timestamp: u64,
+	pub duration: u64,
+	pub operation: &'static str,
+	pub address: String
+}
+
+pub struct SlowLog {
+	entries: VecDeque<SlowLogEntry>,
+	capacity: usize,
+	next_id: u64
+}
+
+impl SlowLog {
+	pub fn new(capacity: usize) -> SlowLog {
+		SlowLog {
+			entries: VecDeque::with_capacity(capacity),
+			capacity: capacity,
+			next_id: 0
+		}
+	}
+
+	pub fn push(self: &mut Self, duration: u64, operation: &'static str, address: String) -> Result<()> {
+		if self.capacity == 0 {
+			return Ok(());
+		}
+
+		if self.entries.len() == self.capacity {
+			self.entries.pop_front();
+		}
+
+		if ARGUMENT.is_verbose {
+			debug!("logged slow {} from {} taking {}us\n", operation, address, duration);
+		}
+
+		self.entries.push_back(SlowLogEntry {
+			id: self.next_id,
+			timestamp: unix_epoch()?,
+			duration: duration,
+			operation: operation,
+			address: address
+		});
+		self.next_id += 1;
+
+		Ok(())
+	}
+
+	pub fn len(self: &Self) -> usize {
+		self.entries.len()
+	}
+
+	pub fn reset(self: &mut Self) {
+		self.entries.clear();
+	}
+
+	// newest first
+	pub fn to_json(self: &Self, count: usize) -> String {
+		let mut json: String = String::from("[");
+
+		for (i, entry) in self.entries.iter().rev().take(count).enumerate() {
+			if i != 0 {
+				json.push(',');
+			}
+
+			json.push_str(&format!("{{\"id\":{},\"timestamp\":{},\"duration\":{},\"operation\":\"{}\",\"address\":\"{}\"}}", entry.id, entry.timestamp, entry.duration, escape_json(entry.operation), escape_json(&entry.address)));
+		}
+
+		json.push(']');
+
+		json
+	}
+}
\ No newline at end of file
@@ -0,0 +1,194 @@
+use std::{
+	cmp::Ordering,
+	collections::{BTreeSet, HashMap},
+	str::from_utf8
+};
+use crate::common::{Result, normalize_range};
+
+// total_cmp gives f64 the total order BTreeSet needs, NaN is kept out at the door anyway
+#[derive(Clone, Copy, PartialEq)]
+pub struct Score(pub f64);
+
+impl Eq for Score {}
+
+impl PartialOrd for Score {
+	fn partial_cmp(self: &Self, other: &Self) -> Option<Ordering> {
+		Some(self.cmp(other))
+	}
+}
+
+impl Ord for Score {
+	fn cmp(self: &Self, other: &Self) -> Ordering {
+		self.0.total_cmp(&other.0)
+	}
+}
+
+// ordered by score then member, with a side map so a member's score is found without a scan
+#[derive(Clone)]
+pub struct SortedSet {
+	order: BTreeSet<(Score, String)>,
+	scores: HashMap<String, f64>
+}
+
+impl SortedSet {
+	pub fn new() -> SortedSet {
+		SortedSet {
+			order: BTreeSet::new(),
+			scores: HashMap::new()
+		}
+	}
+
+	// pairs are laid out as <score:f64> <length:u32> <member>
+	pub fn from_bytes(bytes: &[u8]) -> Result<SortedSet> {
+		let mut sorted_set: SortedSet = SortedSet::new();
+		let mut offset: usize = 0;
+
+		while offset < bytes.len() {
+			if offset + 12 > bytes.len() {
+				return Err(Box::from("sorted set must not be truncated"));
+			}
+
+			let score: f64 = f64::from_be_bytes([bytes[offset], bytes[offset + 1], bytes[offset + 2], bytes[offset + 3], bytes[offset + 4], bytes[offset + 5], bytes[offset + 6], bytes[offset + 7]]);
+			let length: usize = u32::from_be_bytes([bytes[offset + 8], bytes[offset + 9], bytes[offset + 10], bytes[offset + 11]]) as usize;
+			let end: usize = offset + 12 + length;
+
+			if end > bytes.len() {
+				return Err(Box::from("sorted set must not be truncated"));
+			}
+
+			if score.is_nan() {
+				return Err(Box::from("score must be a number"));
+			}
+
+			sorted_set.insert(from_utf8(&bytes[offset + 12..end])?.to_owned(), score);
+			offset = end;
+		}
+
+		Ok(sorted_set)
+	}
+
+	pub fn to_bytes(self: &Self) -> Vec<u8> {
+		let mut bytes: Vec<u8> = Vec::new();
+
+		for (score, member) in &self.order {
+			bytes.extend_from_slice(&score.0.to_be_bytes());
+			bytes.extend_from_slice(&(member.len() as u32).to_be_bytes());
+			bytes.extend_from_slice(member.as_bytes());
+		}
+
+		bytes
+	}
+
+	// true when the member is new rather than rescored
+	pub fn insert(self: &mut Self, member: String, score: f64) -> bool {
+		let is_new: bool = if let Some(previous_score) = self.scores.insert(member.clone(), score) {
+			self.order.remove(&(Score(previous_score), member.clone()));
+
+			false
+		} else {
+			true
+		};
+
+		self.order.insert((Score(score), member));
+
+		is_new
+	}
+
+	pub fn remove(self: &mut Self, member: &str) -> bool {
+		if let Some(score) = self.scores.remove(member) {
+			self.order.remove(&(Score(score), member.to_owned()));
+
+			true
+		} else {
+			false
+		}
+	}
+
+	// lowest scores first from the front, highest first from the back
+	pub fn pop(self: &mut Self, count: usize, is_front: bool) -> Vec<(String, f64)> {
+		let mut pairs: Vec<(String, f64)> = Vec::with_capacity(count.min(self.len()));
+
+		while pairs.len() < count {
+			let (score, member): (Score, String) = if let Some(pair) = if is_front {
+				self.order.pop_first()
+			} else {
+				self.order.pop_last()
+			} {
+				pair
+			} else {
+				break;
+			};
+
+			self.scores.remove(&member);
+			pairs.push((member, score.0));
+		}
+
+		pairs
+	}
+
+	pub fn score(self: &Self, member: &str) -> Option<f64> {
+		self.scores.get(member)
+			.copied()
+	}
+
+	pub fn rank(self: &Self, member: &str) -> Option<usize> {
+		let score: f64 = self.score(member)?;
+
+		Some(self.order.range(..(Score(score), member.to_owned())).count())
+	}
+
+	// inclusive on both ends, negative indices count from the back
+	pub fn range(self: &Self, start: i32, end: i32) -> Vec<String> {
+		let (start, end): (usize, usize) = if let Some(range) = normalize_range(start, end, self.len()) {
+			range
+		} else {
+			return Vec::new();
+		};
+
+		self.order.iter()
+			.skip(start)
+			.take(end - start + 1)
+			.map(|(_, member): &(Score, String)| member.clone())
+			.collect()
+	}
+
+	// both bounds inclusive, a limit of 0 means no limit
+	pub fn range_by_score(self: &Self, minimum: f64, maximum: f64, limit: usize) -> Vec<String> {
+		if minimum > maximum {
+			return Vec::new();
+		}
+
+		self.order.range((Score(minimum), String::new())..)
+			.take_while(|(score, _): &&(Score, String)| score.0 <= maximum)
+			.take(if limit == 0 {
+				usize::MAX
+			} else {
+				limit
+			})
+			.map(|(_, member): &(Score, String)| member.clone())
+			.collect()
+	}
+
+	// only meaningful when every score is equal, "-" and "+" stand for unbounded ends
+	pub fn range_by_lex(self: &Self, minimum: &str, maximum: &str) -> Vec<String> {
+		self.order.iter()
+			.map(|(_, member): &(Score, String)| member)
+			.filter(|member: &&String| (minimum == "-" || member.as_str() >= minimum) && (maximum == "+" || member.as_str() <= maximum))
+			.cloned()
+			.collect()
+	}
+
+	pub fn scores(self: &Self) -> &HashMap<String, f64> {
+		&self.scores
+	}
+
+	pub fn len(self: &Self) -> usize {
+		self.scores.len()
+	}
+
+	pub fn size(self: &Self) -> usize {
+		self.scores.keys()
+			.map(|member: &String| member.len() + 8)
+			.sum()
+	}
+}
\ No newline at end of file
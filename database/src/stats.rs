@@ -0,0 +1,72 @@
+use std::time::Instant;
+
+// counted where they happen and only formatted when INFO asks for them
+pub struct Stats {
+	started_at: Instant,
+	connected_clients: usize,
+	cache_hit_count: u64,
+	cache_miss_count: u64,
+	eviction_count: u64,
+	total_commands_processed: u64
+}
+
+impl Stats {
+	pub fn new() -> Stats {
+		Stats {
+			started_at: Instant::now(),
+			connected_clients: 0,
+			cache_hit_count: 0,
+			cache_miss_count: 0,
+			eviction_count: 0,
+			total_commands_processed: 0
+		}
+	}
+
+	pub fn connect(self: &mut Self) {
+		self.connected_clients += 1;
+	}
+
+	pub fn disconnect(self: &mut Self) {
+		self.connected_clients = self.connected_clients.saturating_sub(1);
+	}
+
+	pub fn hit(self: &mut Self) {
+		self.cache_hit_count += 1;
+	}
+
+	pub fn miss(self: &mut Self) {
+		self.cache_miss_count += 1;
+	}
+
+	pub fn evict(self: &mut Self) {
+		self.eviction_count += 1;
+	}
+
+	pub fn process(self: &mut Self) {
+		self.total_commands_processed += 1;
+	}
+
+	pub fn uptime(self: &Self) -> u64 {
+		self.started_at.elapsed().as_secs()
+	}
+
+	pub fn connected_clients(self: &Self) -> usize {
+		self.connected_clients
+	}
+
+	pub fn cache_hit_count(self: &Self) -> u64 {
+		self.cache_hit_count
+	}
+
+	pub fn cache_miss_count(self: &Self) -> u64 {
+		self.cache_miss_count
+	}
+
+	pub fn eviction_count(self: &Self) -> u64 {
+		self.eviction_count
+	}
+
+	pub fn total_commands_processed(self: &Self) -> u64 {
+		self.total_commands_processed
+	}
+}
\ No newline at end of file
@@ -1,53 +1,326 @@
 use std::{
-	fs::{create_dir_all, exists, read, remove_file, write},
-	path::PathBuf
+	collections::{BTreeSet, HashMap, HashSet},
+	error::Error,
+	fs::{DirEntry, File, create_dir_all, exists, read, read_dir, remove_file},
+	io::{ErrorKind, Write},
+	mem::take,
+	path::{Path, PathBuf},
+	sync::{Mutex, MutexGuard, PoisonError}
 };
 use crate::{
-	common::{ARGUMENT, Result},
-	debug
+	common::{ARGUMENT, NODE_ID_FILE, Result},
+	debug,
+	warn
 };
 
+// expiry sidecars live in their own folder so no key can collide with them and listings skip them as folders
+const TTL_DIRECTORY: &str = ".ttl";
+// keys longer than most file systems allow in a name are stored under their hash, next to a file holding the key
+const HASHED_DIRECTORY: &str = ".hashed";
+const NAME_MAX: usize = 255;
+
+// fnv-1a, since the hasher in std may change between releases and the names must outlive the binary
+fn hash(key: &str) -> u64 {
+	let mut hash: u64 = 0xcbf29ce484222325;
+
+	for byte in key.as_bytes() {
+		hash ^= *byte as u64;
+		hash = hash.wrapping_mul(0x100000001b3);
+	}
+
+	hash
+}
+
+fn file_name(key: &str) -> PathBuf {
+	if key.len() > NAME_MAX {
+		Path::new(HASHED_DIRECTORY).join(format!("{:016x}", hash(key)))
+	} else {
+		PathBuf::from(key)
+	}
+}
+
+fn key_file_name(key: &str) -> PathBuf {
+	Path::new(HASHED_DIRECTORY).join(format!("{:016x}.key", hash(key)))
+}
+
+// keys are file names, so one naming the node id, a folder of ours or a path elsewhere must not get through
+fn is_reserved(key: &str) -> bool {
+	key == NODE_ID_FILE || key == TTL_DIRECTORY || key == HASHED_DIRECTORY || key == "." || key == ".." || key.contains(['/', '\\'])
+}
+
+// another key already owns the hashed name, which a 64-bit hash makes unlikely but not impossible
+fn is_collision(root: &Path, key: &str) -> Result<bool> {
+	if key.len() <= NAME_MAX {
+		return Ok(false);
+	}
+
+	let file: PathBuf = root.join(key_file_name(key));
+
+	Ok(exists(&file)? && read(&file)? != key.as_bytes())
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum FsyncPolicy {
+	Always,
+	EverySecond,
+	No
+}
+
+// the sidecars of the root kept in memory, so an expiry pass reads the due keys off the front instead of the folder
+#[derive(Default)]
+struct Expiries {
+	by_time: BTreeSet<(u64, String)>,
+	by_key: HashMap<String, u64>
+}
+
+impl Expiries {
+	fn insert(self: &mut Self, key: &str, expires_at: u64) {
+		self.remove(key);
+		self.by_time.insert((expires_at, key.to_owned()));
+		self.by_key.insert(key.to_owned(), expires_at);
+	}
+
+	fn remove(self: &mut Self, key: &str) {
+		if let Some(expires_at) = self.by_key.remove(key) {
+			self.by_time.remove(&(expires_at, key.to_owned()));
+		}
+	}
+
+	fn due(self: &Self, now: u64) -> Vec<String> {
+		self.by_time.iter()
+			.take_while(|(expires_at, _): &&(u64, String)| *expires_at <= now)
+			.map(|(_, key): &(u64, String)| key.clone())
+			.collect()
+	}
+}
+
 pub struct Storage {
-	root: PathBuf
+	root: PathBuf,
+	mirrors: Vec<PathBuf>,
+	fsync_policy: FsyncPolicy,
+	dirty_files: Mutex<HashSet<PathBuf>>,
+	expiries: Mutex<Expiries>
 }
 
 impl Storage {
-	pub fn new(root: &str) -> Result<Storage> {
+	pub fn new(root: &str, mirrors: &[String], fsync_policy: FsyncPolicy) -> Result<Storage> {
 		let root: PathBuf = PathBuf::from(root);
 
-		create_dir_all(&root)?;
+		create_dir_all(root.join(TTL_DIRECTORY).join(HASHED_DIRECTORY))?;
+		create_dir_all(root.join(HASHED_DIRECTORY))?;
 
-		Ok(Storage {
-			root: root
-		})
+		let mut mirror_roots: Vec<PathBuf> = Vec::with_capacity(mirrors.len());
+
+		for mirror in mirrors {
+			let mirror: PathBuf = PathBuf::from(mirror);
+
+			create_dir_all(mirror.join(TTL_DIRECTORY).join(HASHED_DIRECTORY))?;
+			create_dir_all(mirror.join(HASHED_DIRECTORY))?;
+			mirror_roots.push(mirror);
+		}
+
+		let mut storage: Storage = Storage {
+			root: root,
+			mirrors: mirror_roots,
+			fsync_policy: fsync_policy,
+			dirty_files: Mutex::new(HashSet::new()),
+			expiries: Mutex::new(Expiries::default())
+		};
+
+		storage.load_expiries()?;
+
+		Ok(storage)
 	}
 
-	pub fn read(self: &Self, key: &str) -> Result<Option<String>> {
-		let file: PathBuf = self.root.join(key);
+	// only the root is consulted, mirrors follow whatever is deleted from it
+	fn load_expiries(self: &mut Self) -> Result<()> {
+		let mut keys: Vec<String> = Vec::new();
+
+		for entry in read_dir(self.root.join(TTL_DIRECTORY))? {
+			let entry: DirEntry = entry?;
+
+			if !entry.file_type()?.is_file() {
+				continue;
+			}
+
+			if let Ok(key) = entry.file_name().into_string() {
+				keys.push(key);
+			}
+		}
+
+		// hashed sidecars only carry the hash, the key itself comes from the file written alongside the value
+		for entry in read_dir(self.root.join(TTL_DIRECTORY).join(HASHED_DIRECTORY))? {
+			let entry: DirEntry = entry?;
+			let key_file: PathBuf = self.root.join(HASHED_DIRECTORY).join(format!("{}.key", entry.file_name().to_string_lossy()));
+
+			if exists(&key_file)? {
+				keys.push(String::from_utf8(read(&key_file)?)?);
+			}
+		}
+
+		let mut expiries: Expiries = Expiries::default();
+
+		for key in keys {
+			match self.read_ttl(&key) {
+				Ok(Some(expires_at)) => expiries.insert(&key, expires_at),
+				Ok(None) => {},
+				Err(error) => {
+					warn!("{} while loading the expiry of {:?}\n", error, key);
+				}
+			}
+		}
 
 		if ARGUMENT.is_verbose {
-			debug!("read {:?} from {:?}\n", key, file.display());
+			debug!("loaded {} expiries from {:?}\n", expiries.by_key.len(), self.root.display());
+		}
+
+		self.expiries = Mutex::new(expiries);
+
+		Ok(())
+	}
+
+	pub fn set_fsync_policy(self: &mut Self, fsync_policy: FsyncPolicy) {
+		self.fsync_policy = fsync_policy;
+	}
+
+	fn write_file(self: &Self, file: &Path, value: &[u8]) -> Result<()> {
+		let mut handle: File = File::create(file)?;
+
+		handle.write_all(value)?;
+
+		match self.fsync_policy {
+			FsyncPolicy::Always => handle.sync_data()?,
+			FsyncPolicy::EverySecond => {
+				self.dirty_files.lock()
+					.map_err(|error: PoisonError<MutexGuard<'_, HashSet<PathBuf>>>| error.to_string())?
+					.insert(file.to_path_buf());
+			},
+			FsyncPolicy::No => ()
+		}
+
+		Ok(())
+	}
+
+	// called once a second under everysec; a file deleted since its write has nothing left to flush
+	pub fn sync_dirty_files(self: &Self) -> Result<()> {
+		let files: HashSet<PathBuf> = take(&mut *self.dirty_files.lock()
+			.map_err(|error: PoisonError<MutexGuard<'_, HashSet<PathBuf>>>| error.to_string())?);
+
+		for file in files {
+			if let Err(error) = File::open(&file).and_then(|handle: File| handle.sync_data()) {
+				if error.kind() != ErrorKind::NotFound {
+					warn!("{} while syncing {:?}\n", error, file.display());
+				}
+			}
 		}
 
-		Ok(if exists(&file)? {
-			Some(String::from_utf8(read(&file)?)?)
+		Ok(())
+	}
+
+	pub fn read(self: &Self, key: &str) -> Result<Option<Vec<u8>>> {
+		let mut last_error: Option<Box<dyn Error>> = None;
+
+		// the first directory that has the key wins, so a lost or broken primary falls back to mirrors
+		for root in [&self.root].into_iter().chain(&self.mirrors) {
+			let file: PathBuf = root.join(file_name(key));
+
+			match (|| -> Result<Option<Vec<u8>>> {
+				Ok(if !is_reserved(key) && exists(&file)? && !is_collision(root, key)? {
+					Some(read(&file)?)
+				} else {
+					None
+				})
+			})() {
+				Ok(Some(value)) => {
+					if ARGUMENT.is_verbose {
+						debug!("read {:?} from {:?}\n", key, file.display());
+					}
+
+					return Ok(Some(value));
+				},
+				Ok(None) => (),
+				Err(error) => {
+					warn!("{} while reading {:?}\n", error, file.display());
+
+					last_error = Some(error);
+				}
+			}
+		}
+
+		if let Some(error) = last_error {
+			Err(error)
 		} else {
-			None
-		})
+			Ok(None)
+		}
+	}
+
+	// mirrors count as well, matching what read would find
+	pub fn exists(self: &Self, key: &str) -> Result<bool> {
+		for root in [&self.root].into_iter().chain(&self.mirrors) {
+			if !is_reserved(key) && exists(root.join(file_name(key)))? && !is_collision(root, key)? {
+				return Ok(true);
+			}
+		}
+
+		Ok(false)
 	}
 
-	pub fn write(self: &Self, key: &str, value: String) -> Result<()> {
-		let file: PathBuf = self.root.join(key);
+	pub fn write(self: &Self, key: &str, value: &[u8]) -> Result<()> {
+		let file: PathBuf = self.root.join(file_name(key));
+		let is_hashed: bool = key.len() > NAME_MAX;
+
+		if is_reserved(key) {
+			return Err(Box::from("key must not be reserved"));
+		}
+
+		if is_collision(&self.root, key)? {
+			return Err(Box::from("key must not collide with a stored key"));
+		}
 
 		if ARGUMENT.is_verbose {
 			debug!("wrote {:?} to {:?}\n", key, file.display());
 		}
 
-		Ok(write(&file, value)?)
+		if is_hashed {
+			self.write_file(&self.root.join(key_file_name(key)), key.as_bytes())?;
+		}
+
+		self.write_file(&file, value)?;
+
+		for mirror in &self.mirrors {
+			let file: PathBuf = mirror.join(file_name(key));
+
+			if let Err(error) = (|| -> Result<()> {
+				if is_hashed {
+					self.write_file(&mirror.join(key_file_name(key)), key.as_bytes())?;
+				}
+
+				self.write_file(&file, value)
+			})() {
+				warn!("{} while mirroring {:?}\n", error, file.display());
+			} else if ARGUMENT.is_verbose {
+				debug!("mirrored {:?} to {:?}\n", key, file.display());
+			}
+		}
+
+		Ok(())
 	}
 
 	pub fn delete(self: &Self, key: &str) -> Result<bool> {
-		let file: PathBuf = self.root.join(key);
+		let file: PathBuf = self.root.join(file_name(key));
+		let is_hashed: bool = key.len() > NAME_MAX;
+		let mut is_deleted: bool = false;
+
+		if is_reserved(key) {
+			return Err(Box::from("key must not be reserved"));
+		}
+
+		// whatever sits under the hashed name belongs to someone else
+		if is_collision(&self.root, key)? {
+			return Ok(false);
+		}
+
+		self.delete_ttl(key)?;
 
 		if exists(&file)? {
 			if ARGUMENT.is_verbose {
@@ -56,9 +329,184 @@ impl Storage {
 
 			remove_file(&file)?;
 
-			Ok(true)
+			is_deleted = true;
+		}
+
+		if is_hashed && exists(self.root.join(key_file_name(key)))? {
+			remove_file(self.root.join(key_file_name(key)))?;
+		}
+
+		for mirror in &self.mirrors {
+			let file: PathBuf = mirror.join(file_name(key));
+
+			if let Err(error) = (|| -> Result<()> {
+				if exists(&file)? && !is_collision(mirror, key)? {
+					remove_file(&file)?;
+
+					if is_hashed && exists(mirror.join(key_file_name(key)))? {
+						remove_file(mirror.join(key_file_name(key)))?;
+					}
+
+					is_deleted = true;
+				}
+
+				Ok(())
+			})() {
+				warn!("{} while deleting {:?}\n", error, file.display());
+			}
+		}
+
+		Ok(is_deleted)
+	}
+
+	// the primary holds every key, so mirrors are not consulted
+	pub fn list_keys(self: &Self) -> Result<Vec<String>> {
+		let mut keys: Vec<String> = Vec::new();
+
+		for entry in read_dir(&self.root)? {
+			let entry: DirEntry = entry?;
+
+			if !entry.file_type()?.is_file() {
+				continue;
+			}
+
+			if let Ok(key) = entry.file_name().into_string() {
+				if key != NODE_ID_FILE {
+					keys.push(key);
+				}
+			}
+		}
+
+		for entry in read_dir(self.root.join(HASHED_DIRECTORY))? {
+			let entry: DirEntry = entry?;
+
+			if entry.file_name()
+				.to_string_lossy()
+				.ends_with(".key") {
+				keys.push(String::from_utf8(read(entry.path())?)?);
+			}
+		}
+
+		if ARGUMENT.is_verbose {
+			debug!("listed {} keys from {:?}\n", keys.len(), self.root.display());
+		}
+
+		Ok(keys)
+	}
+
+	// the absolute expiry in unix epoch milliseconds, so a restart does not extend it
+	pub fn write_ttl(self: &Self, key: &str, expires_at: u64) -> Result<()> {
+		let value: String = expires_at.to_string();
+
+		if is_reserved(key) {
+			return Err(Box::from("key must not be reserved"));
+		}
+
+		self.write_file(&self.root.join(TTL_DIRECTORY).join(file_name(key)), value.as_bytes())?;
+		self.expiries.lock()
+			.map_err(|error: PoisonError<MutexGuard<'_, Expiries>>| error.to_string())?
+			.insert(key, expires_at);
+
+		for mirror in &self.mirrors {
+			let file: PathBuf = mirror.join(TTL_DIRECTORY).join(file_name(key));
+
+			if let Err(error) = self.write_file(&file, value.as_bytes()) {
+				warn!("{} while mirroring {:?}\n", error, file.display());
+			}
+		}
+
+		Ok(())
+	}
+
+	pub fn read_ttl(self: &Self, key: &str) -> Result<Option<u64>> {
+		let file: PathBuf = self.root.join(TTL_DIRECTORY).join(file_name(key));
+
+		Ok(if !is_reserved(key) && exists(&file)? && !is_collision(&self.root, key)? {
+			Some(String::from_utf8(read(&file)?)?.trim()
+				.parse::<u64>()?)
 		} else {
-			Ok(false)
+			None
+		})
+	}
+
+	// read from memory, so a pass costs nothing beyond the keys that are actually due
+	pub fn expired_keys(self: &Self, now: u64) -> Result<Vec<String>> {
+		Ok(self.expiries.lock()
+			.map_err(|error: PoisonError<MutexGuard<'_, Expiries>>| error.to_string())?
+			.due(now))
+	}
+
+	// the node id is not data, so it survives along with the folders
+	fn clear_directory(directory: &Path) -> Result<usize> {
+		let mut count: usize = 0;
+
+		for entry in read_dir(directory)? {
+			let entry: DirEntry = entry?;
+
+			if entry.file_type()?.is_file() && entry.file_name() != NODE_ID_FILE {
+				remove_file(entry.path())?;
+
+				count += 1;
+			}
 		}
+
+		Ok(count)
+	}
+
+	// mirrors are wiped as well, otherwise a count or listing taken from them would still show the old data
+	pub fn clear(self: &mut Self) -> Result<()> {
+		// a hashed key is two files, its value and its name
+		let count: usize = Storage::clear_directory(&self.root)? + Storage::clear_directory(&self.root.join(HASHED_DIRECTORY))? / 2;
+
+		Storage::clear_directory(&self.root.join(TTL_DIRECTORY))?;
+		Storage::clear_directory(&self.root.join(TTL_DIRECTORY).join(HASHED_DIRECTORY))?;
+		self.expiries = Mutex::new(Expiries::default());
+
+		for mirror in &self.mirrors {
+			for directory in [mirror.clone(), mirror.join(HASHED_DIRECTORY), mirror.join(TTL_DIRECTORY), mirror.join(TTL_DIRECTORY).join(HASHED_DIRECTORY)] {
+				if let Err(error) = Storage::clear_directory(&directory) {
+					warn!("{} while clearing {:?}\n", error, directory.display());
+				}
+			}
+		}
+
+		if ARGUMENT.is_verbose {
+			debug!("cleared {} keys from {:?}\n", count, self.root.display());
+		}
+
+		Ok(())
+	}
+
+	pub fn delete_ttl(self: &Self, key: &str) -> Result<bool> {
+		let file: PathBuf = self.root.join(TTL_DIRECTORY).join(file_name(key));
+		if is_reserved(key) {
+			return Ok(false);
+		}
+
+		let is_deleted: bool = exists(&file)? && !is_collision(&self.root, key)?;
+
+		if is_deleted {
+			remove_file(&file)?;
+		}
+
+		self.expiries.lock()
+			.map_err(|error: PoisonError<MutexGuard<'_, Expiries>>| error.to_string())?
+			.remove(key);
+
+		for mirror in &self.mirrors {
+			let file: PathBuf = mirror.join(TTL_DIRECTORY).join(file_name(key));
+
+			if let Err(error) = (|| -> Result<()> {
+				if exists(&file)? && !is_collision(mirror, key)? {
+					remove_file(&file)?;
+				}
+
+				Ok(())
+			})() {
+				warn!("{} while deleting {:?}\n", error, file.display());
+			}
+		}
+
+		Ok(is_deleted)
 	}
 }
\ No newline at end of file
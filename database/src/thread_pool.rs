@@ -1,20 +1,73 @@
 use std::{
+	collections::VecDeque,
 	sync::{
+		atomic::{AtomicUsize, Ordering},
 		mpsc::{channel, Receiver, SendError, Sender},
 		Arc,
 		Mutex
 	},
-	thread::{spawn, JoinHandle}
+	thread::{spawn, JoinHandle},
+	time::Instant
 };
 use crate::{
-	common::{ARGUMENT, Job, Result},
+	common::{ARGUMENT, Result, Task},
 	debug,
 	error
 };
 
+const WAIT_TIME_WINDOW: usize = 1024;
+
+pub struct Telemetry {
+	queue_depth: AtomicUsize,
+	wait_times: Mutex<VecDeque<u64>>
+}
+
+impl Telemetry {
+	pub fn new() -> Telemetry {
+		Telemetry {
+			queue_depth: AtomicUsize::new(0),
+			wait_times: Mutex::new(VecDeque::with_capacity(WAIT_TIME_WINDOW))
+		}
+	}
+
+	fn record(self: &Self, wait_time: u64) {
+		if let Ok(mut wait_times) = self.wait_times.lock() {
+			if wait_times.len() == WAIT_TIME_WINDOW {
+				wait_times.pop_front();
+			}
+
+			wait_times.push_back(wait_time);
+		}
+	}
+
+	pub fn queue_depth(self: &Self) -> usize {
+		self.queue_depth.load(Ordering::Relaxed)
+	}
+
+	// mean and p99 over the last WAIT_TIME_WINDOW tasks, in microseconds
+	pub fn wait_times(self: &Self) -> (u64, u64) {
+		let mut wait_times: Vec<u64> = if let Ok(wait_times) = self.wait_times.lock() {
+			wait_times.iter()
+				.copied()
+				.collect::<Vec<u64>>()
+		} else {
+			return (0, 0);
+		};
+
+		if wait_times.len() == 0 {
+			return (0, 0);
+		}
+
+		wait_times.sort_unstable();
+
+		(wait_times.iter().sum::<u64>() / wait_times.len() as u64, wait_times[(wait_times.len() * 99 - 1) / 100])
+	}
+}
+
 pub struct ThreadPool {
 	threads: Vec<JoinHandle<()>>,
-	sender: Option<Sender<Job>>
+	sender: Option<Sender<Task>>,
+	telemetry: Arc<Telemetry>
 }
 
 impl ThreadPool {
@@ -23,16 +76,18 @@ impl ThreadPool {
 			return Err(Box::from("size must be greater than 0"));
 		}
 
-		let (sender, receiver): (Sender<Job>, Receiver<Job>) = channel();
-		let receiver: Arc<Mutex<Receiver<Job>>> = Arc::new(Mutex::new(receiver));
+		let (sender, receiver): (Sender<Task>, Receiver<Task>) = channel();
+		let receiver: Arc<Mutex<Receiver<Task>>> = Arc::new(Mutex::new(receiver));
+		let telemetry: Arc<Telemetry> = Arc::new(Telemetry::new());
 
 		let mut threads: Vec<JoinHandle<()>> = Vec::with_capacity(size);
 
 		for id in 0..size {
-			let receiver: Arc<Mutex<Receiver<Job>>> = receiver.clone();
+			let receiver: Arc<Mutex<Receiver<Task>>> = receiver.clone();
+			let telemetry: Arc<Telemetry> = telemetry.clone();
 
 			threads.push(spawn(move || loop {
-				let job: Job = if let Ok(job) = (match receiver.lock() {
+				let (enqueued_at, job): Task = if let Ok(task) = (match receiver.lock() {
 					Ok(guard) => guard,
 					Err(error) => {
 						if ARGUMENT.is_verbose {
@@ -42,7 +97,7 @@ impl ThreadPool {
 						break; // break if lock is poisoned (extremely rare)
 					}
 				}).recv() {
-					job
+					task
 				} else {
 					if ARGUMENT.is_verbose {
 						debug!("thread {} shutdown\n", id);
@@ -51,8 +106,13 @@ impl ThreadPool {
 					break;
 				};
 
+				let wait_time: u64 = enqueued_at.elapsed().as_micros() as u64;
+
+				telemetry.queue_depth.fetch_sub(1, Ordering::Relaxed);
+				telemetry.record(wait_time);
+
 				if ARGUMENT.is_verbose {
-					debug!("thread {} got job\n", id);
+					debug!("thread {} got job after {}us\n", id, wait_time);
 				}
 
 				job();
@@ -65,17 +125,28 @@ impl ThreadPool {
 
 		Ok(ThreadPool {
 			threads: threads,
-			sender: Some(sender)
+			sender: Some(sender),
+			telemetry: telemetry
 		})
 	}
 
-	pub fn execute<F>(self: &Self, function: F) -> Result<(), SendError<Job>> where F: FnOnce() + Send + 'static {
+	pub fn execute<F>(self: &Self, function: F) -> Result<(), SendError<Task>> where F: FnOnce() + Send + 'static {
 		if let Some(sender) = &self.sender {
-			sender.send(Box::new(function))?;
+			self.telemetry.queue_depth.fetch_add(1, Ordering::Relaxed);
+
+			if let Err(error) = sender.send((Instant::now(), Box::new(function))) {
+				self.telemetry.queue_depth.fetch_sub(1, Ordering::Relaxed);
+
+				return Err(error);
+			}
 		}
 
 		Ok(())
 	}
+
+	pub fn telemetry(self: &Self) -> Arc<Telemetry> {
+		self.telemetry.clone()
+	}
 }
 
 impl Drop for ThreadPool {